@@ -0,0 +1,261 @@
+//! Compile-time checked Intcode literals. These are re-exported and wrapped by
+//! `aoc_2019_rs::intcode!` and `aoc_2019_rs::intcode_computer!`; use those instead.
+extern crate proc_macro;
+
+use proc_macro::{Group, Span, TokenStream, TokenTree};
+use std::collections::HashMap;
+
+/// Expands an Intcode program in a string literal to a `&'static [_]` of its memory image.
+/// The program is either a list of numbers separated by commas or whitespace, or assembly:
+///
+/// ```text
+/// // comments run to the end of the line
+/// loop: in @20           // statements are separated by newlines or ';'
+///       add @20, 1, @21  // 5 is immediate, @5 is position, R5 is relative
+///       out @21; jt 1, loop
+/// data 0, 0              // raw values
+/// ```
+///
+/// Labels can be used anywhere a number can; they stand for their address. Malformed or empty
+/// programs are compile errors.
+#[proc_macro]
+pub fn intcode_image(input: TokenStream) -> TokenStream {
+    let (span, result) = match string_literal(input) {
+        Ok((span, s)) => (span, Ok(s)),
+        Err(e) => (Span::call_site(), Err(e)),
+    };
+    let result = result.and_then(|s| {
+        //assembly always starts with a mnemonic or label.
+        let first = s.split(|c: char| c == ',' || c.is_whitespace()).find(|t| !t.is_empty());
+        if first.is_some_and(|t| t.parse::<i64>().is_ok()) {
+            numbers(&s)
+        } else {
+            assemble(&s)
+        }
+    });
+    let result = result.and_then(|mem| {
+        if mem.is_empty() {
+            Err("empty program".to_string())
+        } else {
+            Ok(mem)
+        }
+    });
+    let code = match result {
+        Ok(mem) => {
+            let vals: Vec<String> = mem.iter().map(i64::to_string).collect();
+            format!("{{ let m: &'static [_] = &[{}]; m }}", vals.join(", "))
+        }
+        Err(e) => format!("compile_error!({:?})", format!("intcode: {}", e)),
+    };
+    respan(code.parse().unwrap(), span)
+}
+
+/// Gives every token the literal's span, so errors (and lints, like literals which overflow
+/// the memory type) are reported there.
+fn respan(ts: TokenStream, span: Span) -> TokenStream {
+    ts.into_iter()
+        .map(|mut t| {
+            if let TokenTree::Group(g) = &t {
+                let mut g2 = Group::new(g.delimiter(), respan(g.stream(), span));
+                g2.set_span(span);
+                t = TokenTree::Group(g2);
+            }
+            t.set_span(span);
+            t
+        })
+        .collect()
+}
+
+fn string_literal(input: TokenStream) -> Result<(Span, String), String> {
+    let tokens: Vec<TokenTree> = input.into_iter().collect();
+    let (span, lit) = match &tokens[..] {
+        [TokenTree::Literal(l)] => (l.span(), l.to_string()),
+        //a literal passed through macro_rules arrives wrapped in an invisible group.
+        [TokenTree::Group(g)] => return string_literal(g.stream()),
+        _ => return Err("expected a string literal".to_string()),
+    };
+    if let Some(raw) = lit.strip_prefix('r') {
+        let hashes = raw.find('"').ok_or("expected a string literal")?;
+        return Ok((span, raw[hashes + 1..raw.len() - 1 - hashes].to_string()));
+    }
+    if !lit.starts_with('"') {
+        return Err("expected a string literal".to_string());
+    }
+    Ok((span, unescape(&lit[1..lit.len() - 1])?))
+}
+
+/// The value of the inside of a (non-raw) string literal.
+fn unescape(s: &str) -> Result<String, String> {
+    let mut ans = String::new();
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            ans.push(c);
+            continue;
+        }
+        let bad = |e: &str| format!("bad escape '\\{}'", e);
+        match chars.next() {
+            Some('n') => ans.push('\n'),
+            Some('r') => ans.push('\r'),
+            Some('t') => ans.push('\t'),
+            Some('0') => ans.push('\0'),
+            Some(c @ '\\') | Some(c @ '\'') | Some(c @ '"') => ans.push(c),
+            Some('x') => {
+                let hex: String = chars.by_ref().take(2).collect();
+                match u8::from_str_radix(&hex, 16) {
+                    Ok(b) if hex.len() == 2 && b < 0x80 => ans.push(char::from(b)),
+                    _ => return Err(bad(&format!("x{}", hex))),
+                }
+            }
+            Some('u') => {
+                let mut code = String::new();
+                for c in chars.by_ref() {
+                    code.push(c);
+                    if c == '}' {
+                        break;
+                    }
+                }
+                let digits = code.strip_prefix('{').and_then(|c| c.strip_suffix('}'));
+                let c = digits
+                    .and_then(|d| u32::from_str_radix(&d.replace('_', ""), 16).ok())
+                    .and_then(std::char::from_u32);
+                ans.push(c.ok_or_else(|| bad(&format!("u{}", code)))?);
+            }
+            Some('\n') => {
+                while chars.peek().is_some_and(|c| c.is_whitespace()) {
+                    chars.next();
+                }
+            }
+            Some(c) => return Err(bad(&c.to_string())),
+            None => return Err(bad("")),
+        }
+    }
+    Ok(ans)
+}
+
+fn numbers(s: &str) -> Result<Vec<i64>, String> {
+    s.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|t| !t.is_empty())
+        .enumerate()
+        .map(|(ix, t)| t.parse().map_err(|_| format!("bad value '{}' at index {}", t, ix)))
+        .collect()
+}
+
+/// `(opcode, argument count, which argument is written, if any)`.
+fn mnemonic(m: &str) -> Option<(i64, usize, Option<usize>)> {
+    Some(match m.to_lowercase().as_str() {
+        "add" => (1, 3, Some(2)),
+        "mul" | "mult" => (2, 3, Some(2)),
+        "in" | "input" => (3, 1, Some(0)),
+        "out" | "output" => (4, 1, None),
+        "jt" | "jnz" => (5, 2, None),
+        "jf" | "jz" => (6, 2, None),
+        "lt" => (7, 3, Some(2)),
+        "eq" => (8, 3, Some(2)),
+        "arb" => (9, 1, None),
+        "hlt" | "halt" => (99, 0, None),
+        _ => return None,
+    })
+}
+
+struct Statement<'a> {
+    line: usize,
+    op: &'a str,
+    args: Vec<&'a str>,
+}
+
+impl Statement<'_> {
+    fn len(&self) -> usize {
+        if self.op == "data" {
+            self.args.len()
+        } else {
+            1 + self.args.len()
+        }
+    }
+}
+
+fn assemble(s: &str) -> Result<Vec<i64>, String> {
+    let mut labels = HashMap::new();
+    let mut statements = Vec::new();
+    let mut addr = 0;
+    for (ix, line) in s.lines().enumerate() {
+        let line_no = ix + 1;
+        let code = line.split("//").next().unwrap_or("");
+        for mut st in code.split(';').map(str::trim) {
+            while let Some(colon) = st.find(':') {
+                let label = st[..colon].trim();
+                if label.is_empty() || !label.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                    return Err(format!("line {}: bad label '{}'", line_no, label));
+                }
+                if labels.insert(label, addr).is_some() {
+                    return Err(format!("line {}: label '{}' defined twice", line_no, label));
+                }
+                st = st[colon + 1..].trim();
+            }
+            if st.is_empty() {
+                continue;
+            }
+            let (op, rest) = st.split_at(st.find(char::is_whitespace).unwrap_or(st.len()));
+            let args: Vec<&str> = if rest.trim().is_empty() {
+                Vec::new()
+            } else {
+                rest.split(',').map(str::trim).collect()
+            };
+            let st = Statement {
+                line: line_no,
+                op,
+                args,
+            };
+            addr += st.len() as i64;
+            statements.push(st);
+        }
+    }
+    let value = |st: &Statement, v: &str| -> Result<i64, String> {
+        v.parse()
+            .ok()
+            .or_else(|| labels.get(v).cloned())
+            .ok_or_else(|| format!("line {}: bad value '{}'", st.line, v))
+    };
+    let mut mem = Vec::new();
+    for st in &statements {
+        if st.op == "data" {
+            for a in &st.args {
+                mem.push(value(st, a)?);
+            }
+            continue;
+        }
+        let (opcode, arg_count, written) = mnemonic(st.op)
+            .ok_or_else(|| format!("line {}: unknown mnemonic '{}'", st.line, st.op))?;
+        if st.args.len() != arg_count {
+            return Err(format!(
+                "line {}: '{}' takes {} arguments, given {}",
+                st.line,
+                st.op,
+                arg_count,
+                st.args.len()
+            ));
+        }
+        let mut code = opcode;
+        let mut vals = Vec::new();
+        for (ix, a) in st.args.iter().enumerate() {
+            let relative = a
+                .strip_prefix(|c| c == 'R' || c == 'r')
+                .filter(|v| v.starts_with(|c: char| c.is_ascii_digit() || c == '-'));
+            let (mode, v) = if let Some(p) = a.strip_prefix('@') {
+                (0, p)
+            } else if let Some(p) = relative {
+                (2, p)
+            } else {
+                if written == Some(ix) {
+                    return Err(format!("line {}: can't write to immediate '{}'", st.line, a));
+                }
+                (1, &a[..])
+            };
+            code += mode * 10_i64.pow(ix as u32 + 2);
+            vals.push(value(st, v.trim())?);
+        }
+        mem.push(code);
+        mem.extend(vals);
+    }
+    Ok(mem)
+}
@@ -0,0 +1,251 @@
+use super::compmem::CompMem;
+use super::computer::{Computer, StopReason};
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::io::{self, BufRead, BufReader, Lines, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::str::FromStr;
+use std::thread;
+
+/// How a `Server` talks about its computer's inputs and outputs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BridgeMode {
+    /// Input lines are numbers separated by commas or spaces, and each output is a line.
+    Numeric,
+    /// Input lines are fed in as ASCII, newline included, and outputs are sent as text.
+    /// Outputs which aren't ASCII are sent as `#value n` lines.
+    Ascii,
+}
+
+impl BridgeMode {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Numeric => "numeric",
+            Self::Ascii => "ascii",
+        }
+    }
+}
+
+/// Hosts a computer on a TCP socket; each client gets a fresh copy of it.
+///
+/// The protocol is lines of text. The server starts with `#mode numeric` or `#mode ascii`,
+/// then sends output until the program wants input, when it sends `#input`, or halts, when it
+/// sends `#halt` and hangs up. In ASCII mode, an unfinished last line of output follows
+/// those on the same line, and lines of output starting with `#` have another `#` added. A
+/// bad line of input gets `#error` with the reason, and another `#input`. A program which
+/// hits a bad instruction gets `#error` with where, then `#halt`.
+///
+/// The client is the only source of input, so a default input set on the computer is
+/// ignored; the program waits for the client instead.
+pub struct Server<MemType> {
+    listener: TcpListener,
+    computer: Computer<MemType>,
+    mode: BridgeMode,
+}
+
+impl<MemType> Server<MemType>
+where
+    MemType: CompMem + FromStr + Send + 'static,
+{
+    /// Listens on `addr`, which must be a loopback address.
+    pub fn bind<A: ToSocketAddrs>(
+        addr: A,
+        computer: Computer<MemType>,
+        mode: BridgeMode,
+    ) -> io::Result<Self> {
+        let addrs: Vec<SocketAddr> = addr.to_socket_addrs()?.collect();
+        if let Some(a) = addrs.iter().find(|a| !a.ip().is_loopback()) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} isn't a loopback address", a),
+            ));
+        }
+        Ok(Self {
+            listener: TcpListener::bind(&addrs[..])?,
+            computer,
+            mode,
+        })
+    }
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+    /// Waits for one client, and runs the program for it until it halts or hangs up.
+    pub fn serve_one(&self) -> io::Result<()> {
+        let (stream, _) = self.listener.accept()?;
+        session(stream, self.computer.clone(), self.mode)
+    }
+    /// Serves clients forever, each on its own thread.
+    pub fn serve(&self) -> io::Result<()> {
+        for stream in self.listener.incoming() {
+            let (stream, c, mode) = (stream?, self.computer.clone(), self.mode);
+            thread::spawn(move || session(stream, c, mode));
+        }
+        Ok(())
+    }
+}
+
+fn parse_numbers<MemType: FromStr>(line: &str) -> Result<Vec<MemType>, String> {
+    line.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|t| !t.is_empty())
+        .map(|t| t.parse().map_err(|_| format!("bad value '{}'", t)))
+        .collect()
+}
+
+/// Sends the complete lines of `text`, returning what's left.
+fn send_lines<W: Write>(out: &mut W, text: &str) -> io::Result<String> {
+    let mut rest = text;
+    while let Some(nl) = rest.find('\n') {
+        let escape = if rest.starts_with('#') { "#" } else { "" };
+        writeln!(out, "{}{}", escape, &rest[..nl])?;
+        rest = &rest[nl + 1..];
+    }
+    Ok(rest.to_string())
+}
+
+/// Sends some output, returning any unfinished line of text.
+fn send_output<W: Write, MemType: CompMem>(
+    out: &mut W,
+    vals: &[MemType],
+    mode: BridgeMode,
+) -> io::Result<String> {
+    let mut text = String::new();
+    for &v in vals {
+        if mode == BridgeMode::Numeric {
+            writeln!(out, "{}", v)?;
+            continue;
+        }
+        match u8::try_from(v.as_isize()).ok().filter(u8::is_ascii) {
+            Some(b) => text.push(char::from(b)),
+            None => {
+                text = send_lines(out, &text)?;
+                writeln!(out, "#value {}", v)?;
+            }
+        }
+    }
+    send_lines(out, &text)
+}
+
+fn session<MemType>(stream: TcpStream, mut c: Computer<MemType>, mode: BridgeMode) -> io::Result<()>
+where
+    MemType: CompMem + FromStr,
+{
+    let mut out = stream.try_clone()?;
+    let mut lines = BufReader::new(stream).lines();
+    writeln!(out, "#mode {}", mode.name())?;
+    loop {
+        let stop = c.run_to_breakpoint(&HashSet::new());
+        let partial = send_output(&mut out, &c.take_output(), mode)?;
+        let sep = if partial.is_empty() { "" } else { " " };
+        match stop {
+            StopReason::NeedsInput => writeln!(out, "#input{}{}", sep, partial)?,
+            StopReason::BadInstruction(ip) => {
+                writeln!(out, "#error bad instruction at {}", ip)?;
+                return writeln!(out, "#halt{}{}", sep, partial);
+            }
+            _ => return writeln!(out, "#halt{}{}", sep, partial),
+        }
+        loop {
+            let line = match lines.next() {
+                Some(l) => l?,
+                None => return Ok(()),
+            };
+            let line = line.trim_end_matches('\r');
+            let input = match mode {
+                BridgeMode::Numeric => parse_numbers(line),
+                BridgeMode::Ascii => {
+                    Ok(line.bytes().chain(Some(b'\n')).map(|b| b.into()).collect())
+                }
+            };
+            match input {
+                Ok(i) => {
+                    c.give_input(i);
+                    break;
+                }
+                Err(e) => writeln!(out, "#error {}\n#input", e)?,
+            }
+        }
+    }
+}
+
+/// What the program said between one input and the next.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Reply {
+    /// The outputs in numeric mode, or those which weren't ASCII in ASCII mode.
+    pub values: Vec<i64>,
+    pub text: String,
+    pub halted: bool,
+    /// Why the server rejected the last input, or stopped the program, if it did.
+    pub error: Option<String>,
+}
+
+/// Drives a computer hosted by a `Server`.
+pub struct Client {
+    lines: Lines<BufReader<TcpStream>>,
+    out: TcpStream,
+    mode: BridgeMode,
+}
+
+fn bad_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+impl Client {
+    pub fn connect<A: ToSocketAddrs>(addr: A) -> io::Result<Self> {
+        let stream = TcpStream::connect(addr)?;
+        let out = stream.try_clone()?;
+        let mut lines = BufReader::new(stream).lines();
+        let greeting = lines.next().unwrap_or_else(|| Ok(String::new()))?;
+        let mode = match greeting.as_str() {
+            "#mode numeric" => BridgeMode::Numeric,
+            "#mode ascii" => BridgeMode::Ascii,
+            _ => return Err(bad_data(format!("unexpected greeting '{}'", greeting))),
+        };
+        Ok(Self { lines, out, mode })
+    }
+    pub fn mode(&self) -> BridgeMode {
+        self.mode
+    }
+    /// Reads output until the program wants input or halts. Call this once after connecting
+    /// to get what the program says before its first input.
+    pub fn read_reply(&mut self) -> io::Result<Reply> {
+        let mut r = Reply::default();
+        loop {
+            let line = match self.lines.next() {
+                Some(l) => l?,
+                None => return Err(io::ErrorKind::UnexpectedEof.into()),
+            };
+            if let Some(escaped) = line.strip_prefix("##") {
+                r.text.push('#');
+                r.text.push_str(escaped);
+                r.text.push('\n');
+            } else if let Some(rest) = line
+                .strip_prefix("#input")
+                .or_else(|| line.strip_prefix("#halt"))
+            {
+                r.halted = line.starts_with("#halt");
+                r.text.push_str(rest.strip_prefix(' ').unwrap_or(rest));
+                return Ok(r);
+            } else if let Some(v) = line.strip_prefix("#value ") {
+                r.values
+                    .push(v.parse().map_err(|_| bad_data(line.clone()))?);
+            } else if let Some(e) = line.strip_prefix("#error ") {
+                r.error = Some(e.to_string());
+            } else if self.mode == BridgeMode::Numeric {
+                r.values
+                    .push(line.parse().map_err(|_| bad_data(line.clone()))?);
+            } else {
+                r.text.push_str(&line);
+                r.text.push('\n');
+            }
+        }
+    }
+    /// Sends a line of input, and reads the reply.
+    pub fn send_line(&mut self, line: &str) -> io::Result<Reply> {
+        writeln!(self.out, "{}", line)?;
+        self.read_reply()
+    }
+    pub fn send_values(&mut self, values: &[i64]) -> io::Result<Reply> {
+        let line: Vec<String> = values.iter().map(i64::to_string).collect();
+        self.send_line(&line.join(","))
+    }
+}
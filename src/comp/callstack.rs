@@ -0,0 +1,157 @@
+use super::compmem::CompMem;
+use super::computer::Computer;
+use itertools::Itertools;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// A function activation, as inferred from the relative base. Programs from the Intcode
+/// compiler use the relative base as a stack pointer: a call pushes a return address, jumps
+/// to the function, whose first act is to move the base up by its frame size. It moves it
+/// back down before jumping to the return address it finds there.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Frame {
+    /// Where the function starts: the target of the jump that called it.
+    pub entry: isize,
+    /// The jump that called it, if it was entered by a direct jump.
+    pub call_site: Option<isize>,
+    /// The relative base while the function runs.
+    pub base: isize,
+    /// How far the function moved the base; its locals are the `size` cells below `base`.
+    pub size: isize,
+}
+
+/// Watches a running computer's relative base moves and jumps to keep track of its call
+/// stack and build a call graph. Functions which don't move the relative base are invisible.
+#[derive(Clone, Debug, Default)]
+pub struct CallTrace {
+    stack: Vec<Frame>,
+    /// The first move of the relative base, if it wasn't a call; that's just setting up the
+    /// stack.
+    stack_base: Option<isize>,
+    /// `(from, to)` if the previous instruction was a taken jump to a fixed address.
+    last_jump: Option<(isize, isize)>,
+    /// Number of calls from caller entry (`None` for the top level) to callee entry.
+    graph: BTreeMap<(Option<isize>, isize), usize>,
+    returns: usize,
+}
+
+impl CallTrace {
+    /// Called after each instruction executes, with the instruction pointer and relative
+    /// base before and after. `jump` is `Some(indirect)` if the instruction was a taken jump.
+    pub(crate) fn observe(
+        &mut self,
+        ip: isize,
+        old_rb: isize,
+        new_rb: isize,
+        new_ip: isize,
+        jump: Option<bool>,
+    ) {
+        if new_rb > old_rb {
+            let call = self.last_jump.filter(|&(_, to)| to == ip);
+            if call.is_none() && self.stack.is_empty() && self.stack_base.is_none() {
+                self.stack_base = Some(new_rb);
+            } else {
+                let caller = self.stack.last().map(|f| f.entry);
+                let f = Frame {
+                    entry: call.map_or(ip, |(_, to)| to),
+                    call_site: call.map(|(from, _)| from),
+                    base: new_rb,
+                    size: new_rb - old_rb,
+                };
+                *self.graph.entry((caller, f.entry)).or_insert(0) += 1;
+                self.stack.push(f);
+            }
+        } else if new_rb < old_rb {
+            while self.stack.last().is_some_and(|f| f.base > new_rb) {
+                self.stack.pop();
+            }
+        }
+        self.last_jump = match jump {
+            Some(false) => Some((ip, new_ip)),
+            Some(true) => {
+                self.returns += 1;
+                None
+            }
+            None => None,
+        };
+    }
+    /// The live frames, outermost first.
+    pub fn frames(&self) -> &[Frame] {
+        &self.stack
+    }
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+    pub fn stack_base(&self) -> Option<isize> {
+        self.stack_base
+    }
+    /// How many jumps to computed addresses (returns) have been taken.
+    pub fn returns(&self) -> usize {
+        self.returns
+    }
+    pub fn call_graph(&self) -> &BTreeMap<(Option<isize>, isize), usize> {
+        &self.graph
+    }
+    /// The call graph in graphviz format.
+    pub fn call_graph_dot(&self) -> String {
+        let name = |e: Option<isize>| e.map_or("top".to_string(), |e| format!("fn_{}", e));
+        let edges = self
+            .graph
+            .iter()
+            .map(|(&(from, to), n)| format!("  {} -> {} [label={}];", name(from), name(Some(to)), n))
+            .join("\n");
+        format!("digraph calls {{\n{}\n}}\n", edges)
+    }
+}
+
+/// A frame with the values of its locals at the time of the backtrace.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FrameDump<MemType> {
+    pub frame: Frame,
+    pub locals: Vec<MemType>,
+}
+
+/// The call stack of a computer at some instruction, innermost frame first.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Backtrace<MemType> {
+    pub ip: isize,
+    pub frames: Vec<FrameDump<MemType>>,
+}
+
+impl<MemType> Backtrace<MemType>
+where
+    MemType: CompMem,
+{
+    /// The backtrace of `c`, or `None` if it isn't tracing calls.
+    pub fn of(c: &Computer<MemType>) -> Option<Self> {
+        let t = c.call_trace()?;
+        let frames = t
+            .frames()
+            .iter()
+            .rev()
+            .map(|&frame| FrameDump {
+                frame,
+                locals: (frame.base - frame.size..frame.base).map(|a| c.abs_load(a)).collect(),
+            })
+            .collect();
+        Some(Self {
+            ip: c.instruction_pointer(),
+            frames,
+        })
+    }
+}
+
+impl<MemType: fmt::Display> fmt::Display for Backtrace<MemType> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "at ip {}", self.ip)?;
+        for (ix, d) in self.frames.iter().enumerate() {
+            let fr = d.frame;
+            write!(f, "#{} fn@{}", ix, fr.entry)?;
+            if let Some(s) = fr.call_site {
+                write!(f, " called from {}", s)?;
+            }
+            writeln!(f, ", base {}: [{}]", fr.base, d.locals.iter().join(", "))?;
+        }
+        write!(f, "#{} top level", self.frames.len())
+    }
+}
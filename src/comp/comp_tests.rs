@@ -1,37 +1,38 @@
 use super::*;
+use crate::{intcode, intcode_computer};
 use std::str::FromStr;
 
-pub fn t(input: &str, out_ix: isize) -> isize {
-    Computer::from_str(input).unwrap().run().abs_load(out_ix)
+pub fn t(prog: &[isize], out_ix: isize) -> isize {
+    Computer::new(prog).run().abs_load(out_ix)
 }
 
 #[test]
 pub fn day2_tests() {
-    assert_eq!(t("1,0,0,0,99", 0), 2);
-    assert_eq!(t("2,3,0,3,99", 3), 6);
-    assert_eq!(t("2,4,4,5,99,0", 5), 9801);
-    assert_eq!(t("1,1,1,4,99,5,6,0,99", 0), 30);
+    assert_eq!(t(intcode!("1,0,0,0,99"), 0), 2);
+    assert_eq!(t(intcode!("2,3,0,3,99"), 3), 6);
+    assert_eq!(t(intcode!("2,4,4,5,99,0"), 5), 9801);
+    assert_eq!(t(intcode!("1,1,1,4,99,5,6,0,99"), 0), 30);
 }
 
-pub fn t2(input: &str, i_val: isize) -> isize {
-    let mut c = Computer::from_str(input).unwrap();
+pub fn t2(prog: &[isize], i_val: isize) -> isize {
+    let mut c = Computer::new(prog);
     c.with_input(i_val).run().get_last_output()
 }
 #[test]
 pub fn cmp_tests() {
-    let eq8p = "3,9,8,9,10,9,4,9,99,-1,8";
+    let eq8p = intcode!("3,9,8,9,10,9,4,9,99,-1,8");
     assert_eq!(t2(eq8p, 7), 0);
     assert_eq!(t2(eq8p, 8), 1);
     assert_eq!(t2(eq8p, 9), 0);
-    let lt8p = "3,9,7,9,10,9,4,9,99,-1,8";
+    let lt8p = intcode!("3,9,7,9,10,9,4,9,99,-1,8");
     assert_eq!(t2(lt8p, 7), 1);
     assert_eq!(t2(lt8p, 8), 0);
     assert_eq!(t2(lt8p, 9), 0);
-    let eq8i = "3,3,1108,-1,8,3,4,3,99";
+    let eq8i = intcode!("3,3,1108,-1,8,3,4,3,99");
     assert_eq!(t2(eq8i, 7), 0);
     assert_eq!(t2(eq8i, 8), 1);
     assert_eq!(t2(eq8i, 9), 0);
-    let lt8i = "3,3,1107,-1,8,3,4,3,99";
+    let lt8i = intcode!("3,3,1107,-1,8,3,4,3,99");
     assert_eq!(t2(lt8i, 7), 1);
     assert_eq!(t2(lt8i, 8), 0);
     assert_eq!(t2(lt8i, 9), 0);
@@ -39,8 +40,8 @@ pub fn cmp_tests() {
 
 #[test]
 pub fn jmp_tests() {
-    let p = "3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9";
-    let i = "3,3,1105,-1,9,1101,0,0,12,4,12,99,1";
+    let p = intcode!("3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9");
+    let i = intcode!("3,3,1105,-1,9,1101,0,0,12,4,12,99,1");
 
     assert_eq!(t2(p, 0), 0);
     assert_eq!(t2(p, 1), 1);
@@ -52,8 +53,383 @@ pub fn jmp_tests() {
 
 #[test]
 pub fn d5_test() {
-    let t= "3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99";
+    let t = intcode!("3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99");
     assert_eq!(t2(t, 7), 999);
     assert_eq!(t2(t, 8), 1000);
     assert_eq!(t2(t, 9), 1001);
 }
+
+#[test]
+pub fn inspector_tests() {
+    //read input into @20, count inputs in @21, loop.
+    let mut c: Computer = intcode_computer!("3,20,1001,21,1,21,1105,1,0");
+    let mut insp = MemoryInspector::new();
+    c.with_input(5).run_to_input();
+    insp.capture(&c, "first");
+    c.with_input(7).run_to_input();
+    insp.capture(&c, "second");
+    assert_eq!(insp.diff(0, 1), vec![(20, 5, 7), (21, 1, 2)]);
+    assert_eq!(insp.search(&[5, 7]), vec![20]);
+    assert_eq!(insp.search_always_changing(), vec![20, 21]);
+    assert_eq!(insp.find_and_name("count", &[1, 2]), Some(21));
+    assert_eq!(insp.find_and_name("nothing", &[3, 4]), None);
+    assert_eq!(insp.write(&mut c, "count", 10), Some(21));
+    c.with_input(0).run_to_input();
+    assert_eq!(insp.read(&c, "count"), Some(11));
+    assert_eq!(insp.write(&mut c, "nothing", 1), None);
+}
+
+#[test]
+pub fn patch_tests() {
+    let text = "# test patches\n[first] Set up\n1 = 12\n2 = 2\n\n[second] Override\n2 = 5\n";
+    let set: PatchSet<i32> = text.parse().unwrap();
+    assert_eq!(set.patches.len(), 2);
+    assert_eq!(set.get("second").unwrap().description, "Override");
+    let both = set.combined("both");
+    assert_eq!(both.writes.values().cloned().collect::<Vec<_>>(), vec![12, 5]);
+    assert_eq!(set.patches[0].then(&set.patches[1]).writes, both.writes);
+    assert_eq!(set.to_string().parse::<PatchSet<i32>>(), Ok(set.clone()));
+
+    let mut c: Computer<i32> = intcode_computer!("1,0,0,0,99");
+    both.apply(&mut c);
+    assert_eq!(c.changed_cells(), vec![(1, 0, 12), (2, 0, 5)]);
+    assert_eq!(Patch::from_changes("again", "", &c).writes, both.writes);
+
+    assert_eq!(
+        "[p] x\n1 = a".parse::<PatchSet<i32>>(),
+        Err(PatchError::Parse(2, "1 = a".to_string()))
+    );
+    assert_eq!(
+        "1 = 2".parse::<PatchSet<i32>>(),
+        Err(PatchError::Parse(1, "1 = 2".to_string()))
+    );
+}
+
+#[test]
+pub fn search_tests() {
+    //output the product of two inputs.
+    let c: Computer<i32> = intcode_computer!("3,20,3,21,2,20,21,22,4,22,99");
+    let space: Vec<(i32, i32)> = (1..10).flat_map(|a| (1..10).map(move |b| (a, b))).collect();
+    let setup = |c: &mut Computer<i32>, &(a, b): &(i32, i32)| {
+        c.with_input(a).with_input(b);
+    };
+    let found = par_find(&c, space.clone(), setup, |c, _| {
+        Some(c.run().get_last_output()).filter(|&x| x == 12)
+    });
+    assert_eq!(found, Some(((2, 6), 12)));
+    let all = par_map(&c, space, setup, |c, _| c.run().get_last_output());
+    assert_eq!(all.len(), 81);
+    assert!(all.iter().all(|&((a, b), r)| a * b == r));
+}
+
+struct Echo(usize);
+impl Protocol<i32> for Echo {
+    type Command = i32;
+    type Response = i32;
+    const RESPONSE_LEN: usize = 1;
+    fn encode(&self, cmd: &i32) -> Vec<i32> {
+        vec![*cmd; self.0]
+    }
+    fn decode(&self, o: &[i32]) -> Result<i32, DeviceError<i32>> {
+        Some(o[0]).filter(|&x| x >= 0).ok_or(DeviceError::UnknownCode(o[0]))
+    }
+}
+
+#[test]
+pub fn device_tests() {
+    //add one to each input, forever.
+    let mut c: Computer<i32> = intcode_computer!("3,20,1001,20,1,21,4,21,1105,1,0");
+    assert_eq!(Echo(1).call(&mut c, &4), Ok(5));
+    assert_eq!(Echo(1).call(&mut c, &-3), Err(DeviceError::UnknownCode(-2)));
+    assert_eq!(
+        Echo(2).call(&mut c, &1),
+        Err(DeviceError::WrongOutputCount { expected: 1, got: vec![2, 2] })
+    );
+    //echo one input, then halt.
+    let once: Computer<i32> = intcode_computer!("3,20,4,20,99");
+    let mut c = once.clone();
+    assert_eq!(Echo(1).call(&mut c, &7), Ok(7));
+    assert_eq!(Echo(1).call(&mut c, &7), Err(DeviceError::Halted));
+    let mut d = Device::new(once, Echo(1));
+    assert_eq!(d.call(&8), Ok(8));
+    assert_eq!(d.call(&9), Ok(9));
+    assert_eq!(d.calls(), 2);
+}
+
+#[test]
+pub fn diagnostics_tests() {
+    //two self-tests: 0 (immediate), then input*3 (position, written by the mul at 2), then 42.
+    let c: Computer<i32> = intcode_computer!("3,20,1002,20,3,21,104,0,4,21,104,42,99");
+    let ok = run_diagnostics(&c, 0);
+    assert!(ok.is_pass());
+    assert_eq!((ok.passed, ok.passed_answer()), (2, Some(42)));
+    let bad = run_diagnostics(&c, 2);
+    assert!(!bad.is_pass());
+    assert_eq!(bad.passed_answer(), None);
+    assert_eq!(bad.answer, Some(42));
+    let f = &bad.failures[0];
+    assert_eq!((f.output_ix, f.value, f.output_ip), (1, 6, 8));
+    assert_eq!(f.failing_ip, Some(2));
+    assert!(f.failing_op.as_ref().unwrap().starts_with("Mult"));
+    assert!(bad.to_string().starts_with("1 passed, 1 failed"));
+}
+
+#[test]
+pub fn call_trace_tests() {
+    //set up the stack at 100, then call f(7), which outputs 7+1 and returns to 13.
+    let mut c: Computer<i32> = intcode_computer!(
+        "109,100,21101,7,0,1,21101,13,0,0,1105,1,20,99,0,0,0,0,0,0,\
+         109,3,22101,1,-2,-1,204,-1,109,-3,2106,0,0"
+    );
+    c.with_call_trace();
+    let bps = [26].iter().cloned().collect();
+    assert_eq!(c.run_to_breakpoint(&bps), StopReason::Breakpoint(26));
+    let bt = Backtrace::of(&c).unwrap();
+    let f = Frame {
+        entry: 20,
+        call_site: Some(10),
+        base: 103,
+        size: 3,
+    };
+    assert_eq!(bt.frames, vec![FrameDump { frame: f, locals: vec![13, 7, 8] }]);
+    assert_eq!(
+        bt.to_string(),
+        "at ip 26\n#0 fn@20 called from 10, base 103: [13, 7, 8]\n#1 top level"
+    );
+    assert_eq!(c.run_to_breakpoint(&bps), StopReason::Halted);
+    assert_eq!(c.get_output(), &[8]);
+    let t = c.call_trace().unwrap();
+    assert_eq!((t.depth(), t.returns(), t.stack_base()), (0, 1, Some(100)));
+    assert_eq!(t.call_graph().get(&(None, 20)), Some(&1));
+    assert!(t.call_graph_dot().contains("top -> fn_20 [label=1];"));
+
+    let mut c: Computer<i32> = intcode_computer!("3,0,99");
+    assert_eq!(c.run_to_breakpoint(&bps), StopReason::NeedsInput);
+    assert_eq!(Backtrace::of(&c), None);
+    let mut c: Computer<i32> = intcode_computer!("1105,1,3,77");
+    assert_eq!(c.run_to_breakpoint(&bps), StopReason::BadInstruction(3));
+}
+
+fn run_compiled(src: &str, input: &[i64]) -> Vec<i64> {
+    let mut c: Computer<i64> = Computer::new(&compile(src).unwrap());
+    c.give_input(input.to_vec()).run();
+    c.get_output().to_vec()
+}
+
+#[test]
+pub fn compiler_expr_tests() {
+    let src = "fn main() {
+        var a = input();
+        var b = input();
+        output(a + b * 2); output(a - b); output(-a); output(-(a - 10));
+        output(a < b); output(a > b); output(a <= 7); output(a >= b);
+        output(a == b); output(a != b); output(!a); output(!!a);
+        output(a && b); output(a && 0); output(0 || b); output(0 || 0);
+        output((a + 1) * (b - 1));
+    }";
+    assert_eq!(
+        run_compiled(src, &[7, 3]),
+        vec![13, 4, -7, 3, 0, 1, 1, 1, 0, 1, 0, 1, 1, 0, 1, 0, 16]
+    );
+}
+
+#[test]
+pub fn compiler_control_tests() {
+    let fib = "
+        // the slow way, to exercise the stack.
+        fn fib(n) {
+            if (n < 2) { return n; }
+            return fib(n - 1) + fib(n - 2);
+        }
+        fn main() {
+            var i = 0;
+            while (i < 10) { output(fib(i)); i = i + 1; }
+        }";
+    assert_eq!(run_compiled(fib, &[]), vec![0, 1, 1, 2, 3, 5, 8, 13, 21, 34]);
+    let globals = "
+        var total = -5;
+        fn add(x) { total = total + x; }
+        fn sign(x) {
+            if (x < 0) { return -1; } else if (x == 0) { return 0; }
+            return 1;
+        }
+        fn main() {
+            var n = input();
+            while (n > 0) { add(n); n = n - 1; }
+            output(total);
+            output(sign(-4)); output(sign(0)); output(sign(total));
+        }";
+    assert_eq!(run_compiled(globals, &[10]), vec![50, -1, 0, 1]);
+}
+
+#[test]
+pub fn compiler_tooling_tests() {
+    let src = "fn sq(x) { var y = x * x; return y; } fn main() { output(sq(input())); }";
+    let mut c: Computer<i32> = Computer::new(&compile(src).unwrap());
+    assert!(c.disassembly().contains("Move Relative Base"));
+    c.with_call_trace().with_input(9).run();
+    assert_eq!(c.get_output(), &[81]);
+    let t = c.call_trace().unwrap();
+    assert_eq!((t.depth(), t.returns(), t.call_graph().len()), (0, 2, 2));
+}
+
+#[test]
+pub fn compiler_error_tests() {
+    let err = |s| compile::<i64>(s).unwrap_err();
+    assert_eq!(err("fn main() { output(x); }"), CompileError::UndefinedVariable("x".to_string()));
+    assert_eq!(err("fn main() { f(); }"), CompileError::UndefinedFunction("f".to_string()));
+    assert_eq!(
+        err("fn f(a) {} fn main() { f(); }"),
+        CompileError::ArgCount { name: "f".to_string(), expected: 1, got: 0 }
+    );
+    assert_eq!(err("fn main() { var a; var a; }"), CompileError::Duplicate("a".to_string()));
+    assert_eq!(err("fn f() {}"), CompileError::NoMain);
+    assert_eq!(
+        err("fn main() {\n  output(1 +);\n}"),
+        CompileError::Syntax(2, "expected an expression, found ')'".to_string())
+    );
+    assert_eq!(err("fn main() { # }"), CompileError::Syntax(1, "unexpected '#'".to_string()));
+    assert_eq!(compile::<i16>("fn main() { output(100000); }"), Err(CompileError::TooBig(100000)));
+}
+
+#[test]
+pub fn image_tests() {
+    let formats = [
+        ImageFormat::Comma,
+        ImageFormat::Whitespace,
+        ImageFormat::Newline,
+        ImageFormat::Binary,
+    ];
+    let small: Vec<i16> = vec![1, -2, 300, i16::MIN, i16::MAX, 0];
+    let big: Vec<i64> = vec![104, 1_125_899_906_842_624, -7, 99];
+    for f in &formats {
+        assert_eq!(f.decode::<i16>(&f.encode(&small).unwrap()), Ok(small.clone()));
+        assert_eq!(f.decode::<i64>(&f.encode(&big).unwrap()), Ok(big.clone()));
+    }
+    assert_eq!(ImageFormat::Binary.encode(&[-2_i16, 258]), Ok(vec![0xfe, 0xff, 2, 1]));
+    assert_eq!(ImageFormat::Newline.encode(&[1_i32, 2]), Ok(b"1\n2\n".to_vec()));
+    assert_eq!(ImageFormat::Whitespace.parse::<i32>(" 1  2\n\t3\n"), Ok(vec![1, 2, 3]));
+    assert_eq!(
+        ImageFormat::Newline.parse::<i32>("1\n\n2\n 3x \n"),
+        Err(ImageError::BadToken { index: 2, token: "3x".to_string() })
+    );
+    assert_eq!(
+        ImageFormat::Binary.decode::<i32>(&[1, 2, 3, 4, 5]),
+        Err(ImageError::Truncated { len: 5, width: 4 })
+    );
+    assert_eq!(ImageFormat::Comma.decode::<i32>(&[0xff, 0xfe]), Err(ImageError::NotText));
+    assert_eq!(ImageFormat::Binary.parse::<i32>("1,2"), Err(ImageError::NotText));
+    assert_eq!(
+        Computer::<i16>::from_str("1,2, 40000 ,4").unwrap_err(),
+        ImageError::BadToken { index: 2, token: "40000".to_string() }
+    );
+
+    //write past the end of the program, then save and reload it.
+    let mut c: Computer<i32> = intcode_computer!("1101,3,4,6,99");
+    c.run();
+    assert_eq!(c.memory_image(), vec![1101, 3, 4, 6, 99, 0, 7]);
+    let path = std::env::temp_dir().join(format!("intcode_image_test_{}", std::process::id()));
+    ImageFormat::Binary.save(&c, &path).unwrap();
+    let loaded: Computer<i32> = ImageFormat::Binary.load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(loaded.initial_memory(), &c.memory_image()[..]);
+    assert!(matches!(ImageFormat::Comma.load::<i32, _>(&path), Err(ImageError::Io(_))));
+}
+
+#[test]
+pub fn intcode_macro_tests() {
+    let numbers: &'static [i64] = intcode!("1,9,10,3, 2,3,11,0\n99,30,40,50");
+    let asm: &'static [i64] = intcode!(
+        "add @9, @10, @3
+         mul @3, @11, @0 // comments are fine
+         hlt; data 30, 40, 50"
+    );
+    assert_eq!(numbers, asm);
+    let parsed = Computer::<i64>::from_str("1,9,10,3,2,3,11,0,99,30,40,50").unwrap();
+    assert_eq!(numbers, parsed.initial_memory());
+    let mut c: Computer<i32> = intcode_computer!(
+        r"loop: in @x
+                add @x, 1, @y
+                out @y
+                jt 1, loop
+          x: y: data 0"
+    );
+    assert_eq!(c.initial_memory()[..9], [3, 11, 1001, 11, 1, 11, 4, 11, 1105]);
+    c.with_input(4).run_to_input();
+    assert_eq!(c.take_output(), vec![5]);
+    let mut c: Computer<i32> = intcode_computer!("arb 100; in R-1; mul R-1, -2, R0; out R0; hlt");
+    assert_eq!(c.with_input(21).run().get_output(), &[-42]);
+    let escaped: &'static [i32] = intcode!("1,\r2\t\x33,\u{34}");
+    assert_eq!(escaped, &[1, 2, 3, 4]);
+}
+
+fn bridged<T, F>(c: Computer<i64>, mode: BridgeMode, client: F) -> T
+where
+    F: FnOnce(Client) -> T,
+{
+    let server = Server::bind("127.0.0.1:0", c, mode).unwrap();
+    let addr = server.local_addr().unwrap();
+    let handle = std::thread::spawn(move || server.serve_one());
+    let ans = client(Client::connect(addr).unwrap());
+    handle.join().unwrap().unwrap();
+    ans
+}
+
+#[test]
+pub fn bridge_numeric_tests() {
+    let doubler: Computer<i64> = intcode_computer!(
+        "loop: in @x
+               jf @x, end
+               mul @x, 2, @x
+               out @x
+               jt 1, loop
+         end:  hlt
+         x:    data 0"
+    );
+    bridged(doubler, BridgeMode::Numeric, |mut cl| {
+        assert_eq!(cl.mode(), BridgeMode::Numeric);
+        assert_eq!(cl.read_reply().unwrap(), Reply::default());
+        assert_eq!(cl.send_values(&[3, -4]).unwrap().values, vec![6, -8]);
+        let r = cl.send_line("5 x").unwrap();
+        assert_eq!(r.error, Some("bad value 'x'".to_string()));
+        assert_eq!(cl.send_line(" 7,").unwrap().values, vec![14]);
+        let r = cl.send_values(&[0]).unwrap();
+        assert!(r.halted && r.values.is_empty());
+    });
+    //the reason a program stopped gets to the client before the server hangs up.
+    let broken: Computer<i64> = intcode_computer!("in @5; out @5; data 77");
+    bridged(broken, BridgeMode::Numeric, |mut cl| {
+        assert_eq!(cl.read_reply().unwrap(), Reply::default());
+        let r = cl.send_values(&[8]).unwrap();
+        assert_eq!(r.values, vec![8]);
+        assert_eq!(r.error, Some("bad instruction at 4".to_string()));
+        assert!(r.halted);
+    });
+    //only loopback addresses are allowed.
+    let halt: Computer<i64> = intcode_computer!("hlt");
+    let open = Server::bind("0.0.0.0:0", halt.clone(), BridgeMode::Numeric);
+    assert_eq!(open.err().map(|e| e.kind()), Some(std::io::ErrorKind::InvalidInput));
+    assert!(Server::bind("localhost:0", halt, BridgeMode::Numeric).is_ok());
+}
+
+#[test]
+pub fn bridge_ascii_tests() {
+    //shifts each letter of a line along one, until it sees 'q'.
+    let src = "fn main() {
+        while (1) {
+            output(35); output(10); output(62); output(32);
+            var c = input();
+            if (c == 113) { output(1000); output(98); output(121); output(101); return; }
+            while (c != 10) { output(c + 1); c = input(); }
+            output(10);
+        }
+    }";
+    let c = Computer::new(&compile(src).unwrap());
+    bridged(c, BridgeMode::Ascii, |mut cl| {
+        let r = cl.read_reply().unwrap();
+        assert_eq!((r.text.as_str(), r.halted), ("#\n> ", false));
+        assert_eq!(cl.send_line("HAL").unwrap().text, "IBM\n#\n> ");
+        let r = cl.send_line("q").unwrap();
+        assert_eq!((r.text.as_str(), r.values, r.halted), ("bye", vec![1000], true));
+    });
+}
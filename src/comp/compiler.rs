@@ -0,0 +1,711 @@
+use super::compmem::CompMem;
+use super::opcode::OpCode;
+use num::NumCast;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CompileError {
+    /// A line (1-based) that doesn't parse, and why.
+    Syntax(usize, String),
+    UndefinedVariable(String),
+    UndefinedFunction(String),
+    ArgCount {
+        name: String,
+        expected: usize,
+        got: usize,
+    },
+    /// A name declared twice.
+    Duplicate(String),
+    NoMain,
+    /// A value which doesn't fit in the target memory type.
+    TooBig(i64),
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Syntax(l, s) => write!(f, "line {}: {}", l, s),
+            Self::UndefinedVariable(n) => write!(f, "undefined variable '{}'", n),
+            Self::UndefinedFunction(n) => write!(f, "undefined function '{}'", n),
+            Self::ArgCount {
+                name,
+                expected,
+                got,
+            } => write!(f, "'{}' takes {} arguments, given {}", name, expected, got),
+            Self::Duplicate(n) => write!(f, "'{}' declared twice", n),
+            Self::NoMain => write!(f, "no 'fn main()'"),
+            Self::TooBig(v) => write!(f, "{} doesn't fit in memory", v),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Tok {
+    Num(i64),
+    Ident(String),
+    Sym(&'static str),
+}
+
+impl fmt::Display for Tok {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Num(n) => write!(f, "{}", n),
+            Self::Ident(i) => write!(f, "'{}'", i),
+            Self::Sym(s) => write!(f, "'{}'", s),
+        }
+    }
+}
+
+//two character symbols first, so they win.
+const SYMBOLS: [&str; 19] = [
+    "<=", ">=", "==", "!=", "&&", "||", "(", ")", "{", "}", ";", ",", "=", "+", "-", "*", "<",
+    ">", "!",
+];
+const KEYWORDS: [&str; 6] = ["fn", "var", "if", "else", "while", "return"];
+
+fn lex(src: &str) -> Result<Vec<(usize, Tok)>, CompileError> {
+    let mut toks = Vec::new();
+    for (ix, line) in src.lines().enumerate() {
+        let line_no = ix + 1;
+        let mut rest = line.split("//").next().unwrap_or("").trim_start();
+        while let Some(c) = rest.chars().next() {
+            let word_len = |p: fn(char) -> bool| rest.find(|c| !p(c)).unwrap_or(rest.len());
+            let len = if c.is_ascii_digit() {
+                let len = word_len(|c| c.is_ascii_digit());
+                let n = rest[..len].parse().map_err(|_| {
+                    CompileError::Syntax(line_no, format!("number too big: {}", &rest[..len]))
+                })?;
+                toks.push((line_no, Tok::Num(n)));
+                len
+            } else if c.is_alphabetic() || c == '_' {
+                let len = word_len(|c| c.is_alphanumeric() || c == '_');
+                toks.push((line_no, Tok::Ident(rest[..len].to_string())));
+                len
+            } else {
+                let s = SYMBOLS
+                    .iter()
+                    .find(|s| rest.starts_with(*s))
+                    .ok_or_else(|| CompileError::Syntax(line_no, format!("unexpected '{}'", c)))?;
+                toks.push((line_no, Tok::Sym(s)));
+                s.len()
+            };
+            rest = rest[len..].trim_start();
+        }
+    }
+    Ok(toks)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum BinOp {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Add,
+    Sub,
+    Mul,
+}
+
+//loosest binding first.
+const LEVELS: [&[(&str, BinOp)]; 6] = [
+    &[("||", BinOp::Or)],
+    &[("&&", BinOp::And)],
+    &[("==", BinOp::Eq), ("!=", BinOp::Ne)],
+    &[("<", BinOp::Lt), ("<=", BinOp::Le), (">", BinOp::Gt), (">=", BinOp::Ge)],
+    &[("+", BinOp::Add), ("-", BinOp::Sub)],
+    &[("*", BinOp::Mul)],
+];
+
+#[derive(Clone, Debug)]
+enum Expr {
+    Num(i64),
+    Var(String),
+    Call(String, Vec<Expr>),
+    Neg(Box<Expr>),
+    Not(Box<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Clone, Debug)]
+enum Stmt {
+    Var(String, Option<Expr>),
+    Assign(String, Expr),
+    If(Expr, Vec<Stmt>, Vec<Stmt>),
+    While(Expr, Vec<Stmt>),
+    Return(Option<Expr>),
+    Expr(Expr),
+}
+
+#[derive(Clone, Debug)]
+struct Function {
+    name: String,
+    params: Vec<String>,
+    body: Vec<Stmt>,
+}
+
+#[derive(Clone, Debug, Default)]
+struct Program {
+    globals: Vec<(String, i64)>,
+    functions: Vec<Function>,
+}
+
+struct Parser {
+    toks: Vec<(usize, Tok)>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Tok> {
+        self.toks.get(self.pos).map(|t| &t.1)
+    }
+    fn error(&self, expected: &str) -> CompileError {
+        let line = self.toks.get(self.pos).or_else(|| self.toks.last()).map_or(1, |t| t.0);
+        let found = self.peek().map_or("end of input".to_string(), Tok::to_string);
+        CompileError::Syntax(line, format!("expected {}, found {}", expected, found))
+    }
+    fn is_sym(&self, s: &str) -> bool {
+        matches!(self.peek(), Some(Tok::Sym(x)) if *x == s)
+    }
+    fn is_keyword(&self, k: &str) -> bool {
+        matches!(self.peek(), Some(Tok::Ident(x)) if x == k)
+    }
+    fn eat_sym(&mut self, s: &str) -> bool {
+        let found = self.is_sym(s);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+    fn eat_keyword(&mut self, k: &str) -> bool {
+        let found = self.is_keyword(k);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+    fn expect_sym(&mut self, s: &str) -> Result<(), CompileError> {
+        if self.eat_sym(s) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("'{}'", s)))
+        }
+    }
+    fn ident(&mut self) -> Result<String, CompileError> {
+        match self.peek() {
+            Some(Tok::Ident(i)) if !KEYWORDS.contains(&i.as_str()) => {
+                let i = i.clone();
+                self.pos += 1;
+                Ok(i)
+            }
+            _ => Err(self.error("a name")),
+        }
+    }
+    /// A list of `item`s between brackets, separated by commas.
+    fn list<T, F>(&mut self, mut item: F) -> Result<Vec<T>, CompileError>
+    where
+        F: FnMut(&mut Self) -> Result<T, CompileError>,
+    {
+        self.expect_sym("(")?;
+        let mut ans = Vec::new();
+        if !self.eat_sym(")") {
+            loop {
+                ans.push(item(self)?);
+                if self.eat_sym(")") {
+                    break;
+                }
+                self.expect_sym(",")?;
+            }
+        }
+        Ok(ans)
+    }
+    fn program(&mut self) -> Result<Program, CompileError> {
+        let mut p = Program::default();
+        while self.peek().is_some() {
+            if self.eat_keyword("var") {
+                let name = self.ident()?;
+                let mut value = 0;
+                if self.eat_sym("=") {
+                    let neg = self.eat_sym("-");
+                    value = match self.peek() {
+                        Some(&Tok::Num(n)) => n,
+                        _ => return Err(self.error("a number")),
+                    };
+                    self.pos += 1;
+                    if neg {
+                        value = -value;
+                    }
+                }
+                self.expect_sym(";")?;
+                p.globals.push((name, value));
+            } else if self.eat_keyword("fn") {
+                let name = self.ident()?;
+                let params = self.list(Self::ident)?;
+                let body = self.block()?;
+                p.functions.push(Function { name, params, body });
+            } else {
+                return Err(self.error("'fn' or 'var'"));
+            }
+        }
+        Ok(p)
+    }
+    fn block(&mut self) -> Result<Vec<Stmt>, CompileError> {
+        self.expect_sym("{")?;
+        let mut ans = Vec::new();
+        while !self.eat_sym("}") {
+            ans.push(self.statement()?);
+        }
+        Ok(ans)
+    }
+    fn statement(&mut self) -> Result<Stmt, CompileError> {
+        let s = if self.eat_keyword("var") {
+            let name = self.ident()?;
+            let init = if self.eat_sym("=") {
+                Some(self.expr()?)
+            } else {
+                None
+            };
+            Stmt::Var(name, init)
+        } else if self.eat_keyword("if") {
+            let cond = self.condition()?;
+            let then = self.block()?;
+            let otherwise = if !self.eat_keyword("else") {
+                Vec::new()
+            } else if self.is_keyword("if") {
+                vec![self.statement()?]
+            } else {
+                self.block()?
+            };
+            return Ok(Stmt::If(cond, then, otherwise));
+        } else if self.eat_keyword("while") {
+            let cond = self.condition()?;
+            return Ok(Stmt::While(cond, self.block()?));
+        } else if self.eat_keyword("return") {
+            Stmt::Return(if self.is_sym(";") {
+                None
+            } else {
+                Some(self.expr()?)
+            })
+        } else if self.toks.get(self.pos + 1).map(|t| &t.1) == Some(&Tok::Sym("=")) {
+            let name = self.ident()?;
+            self.pos += 1;
+            Stmt::Assign(name, self.expr()?)
+        } else {
+            Stmt::Expr(self.expr()?)
+        };
+        self.expect_sym(";")?;
+        Ok(s)
+    }
+    fn condition(&mut self) -> Result<Expr, CompileError> {
+        self.expect_sym("(")?;
+        let e = self.expr()?;
+        self.expect_sym(")")?;
+        Ok(e)
+    }
+    fn expr(&mut self) -> Result<Expr, CompileError> {
+        self.binary(0)
+    }
+    fn binary(&mut self, level: usize) -> Result<Expr, CompileError> {
+        if level == LEVELS.len() {
+            return self.unary();
+        }
+        let mut lhs = self.binary(level + 1)?;
+        while let Some(&(_, op)) = LEVELS[level].iter().find(|(s, _)| self.is_sym(s)) {
+            self.pos += 1;
+            let rhs = self.binary(level + 1)?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+    fn unary(&mut self) -> Result<Expr, CompileError> {
+        if self.eat_sym("-") {
+            Ok(match self.unary()? {
+                Expr::Num(n) => Expr::Num(-n),
+                e => Expr::Neg(Box::new(e)),
+            })
+        } else if self.eat_sym("!") {
+            Ok(Expr::Not(Box::new(self.unary()?)))
+        } else {
+            self.primary()
+        }
+    }
+    fn primary(&mut self) -> Result<Expr, CompileError> {
+        if let Some(&Tok::Num(n)) = self.peek() {
+            self.pos += 1;
+            return Ok(Expr::Num(n));
+        }
+        if self.is_sym("(") {
+            return self.condition();
+        }
+        let name = self.ident().map_err(|_| self.error("an expression"))?;
+        if self.is_sym("(") {
+            let args = self.list(Self::expr)?;
+            Ok(Expr::Call(name, args))
+        } else {
+            Ok(Expr::Var(name))
+        }
+    }
+}
+
+/// An instruction argument, before addresses are known.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Operand {
+    Imm(i64),
+    /// The address of a label, as an immediate.
+    Label(usize),
+    /// A global variable; global 0 holds function return values.
+    Global(usize),
+    /// A cell in the current function's frame.
+    Slot(usize),
+    /// Relative to the top of the current frame, where a callee's frame will go.
+    Rel(i64),
+    /// The current function's frame size, times this.
+    FrameSize(i64),
+    /// Where the stack starts, after the code and globals.
+    StackBase,
+}
+use Operand::*;
+
+const RET: Operand = Global(0);
+
+#[derive(Clone, Debug)]
+enum Item {
+    Ins(OpCode, Vec<Operand>),
+    Mark(usize),
+}
+
+#[derive(Clone, Debug, Default)]
+struct Chunk {
+    items: Vec<Item>,
+    frame_size: usize,
+}
+
+/// Generates code for one function at a time. Frames are laid out with the return address
+/// in slot 0, then the parameters, then locals, then temporaries.
+#[derive(Default)]
+struct Gen {
+    functions: HashMap<String, (usize, usize)>,
+    globals: HashMap<String, usize>,
+    labels: usize,
+    locals: HashMap<String, usize>,
+    in_scope: HashSet<String>,
+    first_temp: usize,
+    next_slot: usize,
+    max_slot: usize,
+    ret_label: usize,
+    items: Vec<Item>,
+}
+
+fn declarations<'a>(body: &'a [Stmt], ans: &mut Vec<&'a str>) {
+    for s in body {
+        match s {
+            Stmt::Var(n, _) => ans.push(n),
+            Stmt::If(_, a, b) => {
+                declarations(a, ans);
+                declarations(b, ans);
+            }
+            Stmt::While(_, a) => declarations(a, ans),
+            _ => (),
+        }
+    }
+}
+
+impl Gen {
+    fn label(&mut self) -> usize {
+        self.labels += 1;
+        self.labels - 1
+    }
+    fn mark(&mut self, l: usize) {
+        self.items.push(Item::Mark(l));
+    }
+    fn emit(&mut self, op: OpCode, args: &[Operand]) {
+        self.items.push(Item::Ins(op, args.to_vec()));
+    }
+    fn mov(&mut self, from: Operand, to: Operand) {
+        self.emit(OpCode::Add, &[from, Imm(0), to]);
+    }
+    fn jump(&mut self, l: usize) {
+        self.emit(OpCode::JumpIfTrue, &[Imm(1), Label(l)]);
+    }
+    fn temp(&mut self) -> Operand {
+        self.next_slot += 1;
+        self.max_slot = self.max_slot.max(self.next_slot);
+        Slot(self.next_slot - 1)
+    }
+    fn var(&self, name: &str) -> Result<Operand, CompileError> {
+        if self.in_scope.contains(name) {
+            Ok(Slot(self.locals[name]))
+        } else if let Some(&g) = self.globals.get(name) {
+            Ok(Global(g))
+        } else {
+            Err(CompileError::UndefinedVariable(name.to_string()))
+        }
+    }
+    /// Calls `label` with `args`, leaving the result in `RET`.
+    fn call_label(&mut self, label: usize, args: &[Operand]) {
+        for (ix, &a) in args.iter().enumerate() {
+            self.mov(a, Rel(ix as i64 + 1));
+        }
+        let ret = self.label();
+        self.mov(Label(ret), Rel(0));
+        self.jump(label);
+        self.mark(ret);
+    }
+    fn call(&mut self, name: &str, args: &[Expr]) -> Result<Operand, CompileError> {
+        let arity = match name {
+            "input" => 0,
+            "output" => 1,
+            _ => self
+                .functions
+                .get(name)
+                .ok_or_else(|| CompileError::UndefinedFunction(name.to_string()))?
+                .1,
+        };
+        if arity != args.len() {
+            return Err(CompileError::ArgCount {
+                name: name.to_string(),
+                expected: arity,
+                got: args.len(),
+            });
+        }
+        let args = args.iter().map(|a| self.expr(a)).collect::<Result<Vec<_>, _>>()?;
+        Ok(match name {
+            "input" => {
+                let t = self.temp();
+                self.emit(OpCode::Input, &[t]);
+                t
+            }
+            "output" => {
+                self.emit(OpCode::Output, &[args[0]]);
+                Imm(0)
+            }
+            _ => {
+                self.call_label(self.functions[name].0, &args);
+                let t = self.temp();
+                self.mov(RET, t);
+                t
+            }
+        })
+    }
+    fn expr(&mut self, e: &Expr) -> Result<Operand, CompileError> {
+        let (a, b) = match e {
+            Expr::Num(n) => return Ok(Imm(*n)),
+            Expr::Var(name) => return self.var(name),
+            Expr::Call(name, args) => return self.call(name, args),
+            Expr::Neg(a) | Expr::Not(a) => (self.expr(a)?, Imm(0)),
+            Expr::Binary(_, a, b) => (self.expr(a)?, self.expr(b)?),
+        };
+        let t = self.temp();
+        let not_t = |g: &mut Self| g.emit(OpCode::Equals, &[t, Imm(0), t]);
+        match e {
+            Expr::Neg(_) => self.emit(OpCode::Mult, &[a, Imm(-1), t]),
+            Expr::Not(_) => self.emit(OpCode::Equals, &[a, Imm(0), t]),
+            Expr::Binary(op, _, _) => match op {
+                BinOp::Add => self.emit(OpCode::Add, &[a, b, t]),
+                BinOp::Sub => {
+                    self.emit(OpCode::Mult, &[b, Imm(-1), t]);
+                    self.emit(OpCode::Add, &[a, t, t]);
+                }
+                BinOp::Mul => self.emit(OpCode::Mult, &[a, b, t]),
+                BinOp::Lt => self.emit(OpCode::LessThan, &[a, b, t]),
+                BinOp::Gt => self.emit(OpCode::LessThan, &[b, a, t]),
+                BinOp::Le => {
+                    self.emit(OpCode::LessThan, &[b, a, t]);
+                    not_t(self);
+                }
+                BinOp::Ge => {
+                    self.emit(OpCode::LessThan, &[a, b, t]);
+                    not_t(self);
+                }
+                BinOp::Eq => self.emit(OpCode::Equals, &[a, b, t]),
+                BinOp::Ne => {
+                    self.emit(OpCode::Equals, &[a, b, t]);
+                    not_t(self);
+                }
+                BinOp::And | BinOp::Or => {
+                    //count the zeros: none for &&, fewer than two for ||.
+                    let u = self.temp();
+                    self.emit(OpCode::Equals, &[a, Imm(0), t]);
+                    self.emit(OpCode::Equals, &[b, Imm(0), u]);
+                    if *op == BinOp::And {
+                        self.emit(OpCode::Add, &[t, u, t]);
+                    } else {
+                        self.emit(OpCode::Mult, &[t, u, t]);
+                    }
+                    not_t(self);
+                }
+            },
+            _ => unreachable!(),
+        }
+        Ok(t)
+    }
+    fn block(&mut self, body: &[Stmt]) -> Result<(), CompileError> {
+        body.iter().try_for_each(|s| self.stmt(s))
+    }
+    fn stmt(&mut self, s: &Stmt) -> Result<(), CompileError> {
+        self.next_slot = self.first_temp;
+        match s {
+            Stmt::Var(name, init) => {
+                let v = match init {
+                    Some(e) => self.expr(e)?,
+                    None => Imm(0),
+                };
+                self.mov(v, Slot(self.locals[name]));
+                self.in_scope.insert(name.clone());
+            }
+            Stmt::Assign(name, e) => {
+                let target = self.var(name)?;
+                let v = self.expr(e)?;
+                self.mov(v, target);
+            }
+            Stmt::If(cond, then, otherwise) => {
+                let (else_l, end_l) = (self.label(), self.label());
+                let c = self.expr(cond)?;
+                self.emit(OpCode::JumpIfFalse, &[c, Label(else_l)]);
+                self.block(then)?;
+                self.jump(end_l);
+                self.mark(else_l);
+                self.block(otherwise)?;
+                self.mark(end_l);
+            }
+            Stmt::While(cond, body) => {
+                let (top_l, end_l) = (self.label(), self.label());
+                self.mark(top_l);
+                let c = self.expr(cond)?;
+                self.emit(OpCode::JumpIfFalse, &[c, Label(end_l)]);
+                self.block(body)?;
+                self.jump(top_l);
+                self.mark(end_l);
+            }
+            Stmt::Return(e) => {
+                let v = match e {
+                    Some(e) => self.expr(e)?,
+                    None => Imm(0),
+                };
+                self.mov(v, RET);
+                self.jump(self.ret_label);
+            }
+            Stmt::Expr(e) => {
+                self.expr(e)?;
+            }
+        }
+        Ok(())
+    }
+    fn function(&mut self, f: &Function) -> Result<Chunk, CompileError> {
+        let mut names: Vec<&str> = f.params.iter().map(String::as_str).collect();
+        declarations(&f.body, &mut names);
+        self.locals.clear();
+        for (ix, n) in names.iter().enumerate() {
+            if self.locals.insert(n.to_string(), ix + 1).is_some() {
+                return Err(CompileError::Duplicate(n.to_string()));
+            }
+        }
+        self.in_scope = f.params.iter().cloned().collect();
+        self.first_temp = names.len() + 1;
+        self.max_slot = self.first_temp;
+        self.ret_label = self.label();
+        self.mark(self.functions[&f.name].0);
+        self.emit(OpCode::MoveRelativeBase, &[FrameSize(1)]);
+        self.block(&f.body)?;
+        self.mov(Imm(0), RET);
+        self.mark(self.ret_label);
+        self.emit(OpCode::MoveRelativeBase, &[FrameSize(-1)]);
+        self.emit(OpCode::JumpIfFalse, &[Imm(0), Rel(0)]);
+        Ok(Chunk {
+            items: std::mem::take(&mut self.items),
+            frame_size: self.max_slot,
+        })
+    }
+}
+
+fn assemble<MemType: CompMem>(chunks: &[Chunk], globals: &[i64]) -> Result<Vec<MemType>, CompileError> {
+    let mut addrs = HashMap::new();
+    let mut len = 0;
+    for item in chunks.iter().flat_map(|c| &c.items) {
+        match item {
+            Item::Mark(l) => {
+                addrs.insert(*l, len);
+            }
+            Item::Ins(_, args) => len += 1 + args.len(),
+        }
+    }
+    let data = len as i64;
+    let mut mem: Vec<i64> = Vec::new();
+    for c in chunks {
+        let frame = c.frame_size as i64;
+        for item in &c.items {
+            if let Item::Ins(op, args) = item {
+                let mut code = *op as i64;
+                let mut vals = Vec::new();
+                for (ix, &a) in args.iter().enumerate() {
+                    let (mode, v) = match a {
+                        Imm(n) => (1, n),
+                        Label(l) => (1, addrs[&l] as i64),
+                        FrameSize(sign) => (1, sign * frame),
+                        StackBase => (1, data + globals.len() as i64),
+                        Global(g) => (0, data + g as i64),
+                        Slot(s) => (2, s as i64 - frame),
+                        Rel(r) => (2, r),
+                    };
+                    code += mode * 10_i64.pow(ix as u32 + 2);
+                    vals.push(v);
+                }
+                mem.push(code);
+                mem.extend(vals);
+            }
+        }
+    }
+    mem.extend(globals);
+    mem.into_iter()
+        .map(|v| NumCast::from(v).ok_or(CompileError::TooBig(v)))
+        .collect()
+}
+
+/// Compiles a small C-like language to an Intcode memory image, for `Computer::new`.
+///
+/// A program is global `var`s (initialised with constants) and `fn`s; it runs `main()`
+/// then halts. Variables are integers; there's `+ - *`, comparisons, `! && ||` (both sides
+/// are always evaluated), `if`/`else`, `while` and `return`. `input()` reads a value and
+/// `output(x)` writes one. Locals are function scoped, and live in frames on a stack
+/// addressed through the relative base.
+pub fn compile<MemType: CompMem>(src: &str) -> Result<Vec<MemType>, CompileError> {
+    let prog = Parser {
+        toks: lex(src)?,
+        pos: 0,
+    }
+    .program()?;
+    let mut g = Gen::default();
+    g.globals.insert(String::new(), 0);
+    for (ix, (name, _)) in prog.globals.iter().enumerate() {
+        if g.globals.insert(name.clone(), ix + 1).is_some() {
+            return Err(CompileError::Duplicate(name.clone()));
+        }
+    }
+    for f in &prog.functions {
+        let l = g.label();
+        let builtin = f.name == "input" || f.name == "output";
+        if builtin || g.functions.insert(f.name.clone(), (l, f.params.len())).is_some() {
+            return Err(CompileError::Duplicate(f.name.clone()));
+        }
+    }
+    let main = match g.functions.get("main") {
+        Some(&(l, 0)) => l,
+        _ => return Err(CompileError::NoMain),
+    };
+    g.emit(OpCode::MoveRelativeBase, &[StackBase]);
+    g.call_label(main, &[]);
+    g.emit(OpCode::Halt, &[]);
+    let mut chunks = vec![Chunk {
+        items: std::mem::take(&mut g.items),
+        frame_size: 0,
+    }];
+    for f in &prog.functions {
+        chunks.push(g.function(f)?);
+    }
+    let globals: Vec<i64> = std::iter::once(0).chain(prog.globals.iter().map(|g| g.1)).collect();
+    assemble(&chunks, &globals)
+}
@@ -1,3 +1,4 @@
+use super::callstack::CallTrace;
 use super::compmem::CompMem;
 use log::info;
 use std::cmp;
@@ -9,6 +10,7 @@ use std::sync::mpsc::{Receiver, Sender};
 use std::sync::{mpsc, Arc, Mutex};
 
 use super::enums::*;
+use super::image::{ImageError, ImageFormat};
 use super::oparg::Arg;
 use super::opcode::OpCode;
 
@@ -27,6 +29,34 @@ pub struct Computer<MemType = i32> {
     relative_base: isize,
     default_input: Option<MemType>,
     ips_since_last_mem_edit: HashSet<isize>,
+    trace: Option<IoTrace<MemType>>,
+    calls: Option<CallTrace>,
+}
+
+/// Where an output value came from: the output instruction, the cell it read (if not an
+/// immediate), and the instruction which last wrote that cell.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OutputRecord<MemType> {
+    pub ip: isize,
+    pub value: MemType,
+    pub source: Option<isize>,
+    pub writer: Option<isize>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct IoTrace<MemType> {
+    pub last_writer: HashMap<isize, isize>,
+    pub outputs: Vec<OutputRecord<MemType>>,
+}
+
+/// Why `run_to_breakpoint` stopped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StopReason {
+    Breakpoint(isize),
+    Halted,
+    NeedsInput,
+    /// The instruction at this address can't be decoded.
+    BadInstruction(isize),
 }
 
 impl<MT> Clone for Computer<MT>
@@ -48,18 +78,37 @@ where
             output_chan: None,
             default_input: self.default_input,
             ips_since_last_mem_edit: self.ips_since_last_mem_edit.clone(),
+            trace: self.trace.clone(),
+            calls: self.calls.clone(),
         }
     }
+    /// As `clone`, reusing this computer's buffers.
+    fn clone_from(&mut self, source: &Self) {
+        self.name.clone_from(&source.name);
+        self.initial_mem.clone_from(&source.initial_mem);
+        self.memory.clone_from(&source.memory);
+        self.instruction_pointer = source.instruction_pointer;
+        self.state = source.state;
+        self.relative_base = source.relative_base;
+        self.fixed_input.clone_from(&source.fixed_input);
+        self.input_chan = None;
+        self.input_arc = None;
+        self.output.clone_from(&source.output);
+        self.output_chan = None;
+        self.default_input = source.default_input;
+        self.ips_since_last_mem_edit.clone_from(&source.ips_since_last_mem_edit);
+        self.trace.clone_from(&source.trace);
+        self.calls.clone_from(&source.calls);
+    }
 }
 
 impl<MemType> FromStr for Computer<MemType>
 where
     MemType: FromStr + CompMem,
 {
-    type Err = <MemType as std::str::FromStr>::Err;
+    type Err = ImageError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let is: Result<Vec<_>, _> = s.trim().split(',').map(str::parse).collect();
-        Ok(Self::new(&is?))
+        Ok(Self::new(&ImageFormat::Comma.parse(s)?))
     }
 }
 impl<MemType> Computer<MemType>
@@ -81,6 +130,8 @@ where
             relative_base: 0,
             default_input: None,
             ips_since_last_mem_edit: HashSet::new(),
+            trace: None,
+            calls: None,
         };
         c.reset();
         c
@@ -170,6 +221,25 @@ where
         other.with_chan_output(tx);
         self
     }
+    /// Starts recording which instruction wrote each cell, and where each output came from.
+    pub fn with_io_trace(&mut self) -> &mut Self {
+        self.trace = Some(IoTrace {
+            last_writer: HashMap::new(),
+            outputs: Vec::new(),
+        });
+        self
+    }
+    pub fn io_trace(&self) -> Option<&IoTrace<MemType>> {
+        self.trace.as_ref()
+    }
+    /// Starts tracking the call stack, from relative base moves and jumps.
+    pub fn with_call_trace(&mut self) -> &mut Self {
+        self.calls = Some(CallTrace::default());
+        self
+    }
+    pub fn call_trace(&self) -> Option<&CallTrace> {
+        self.calls.as_ref()
+    }
     pub fn with_chan_input(&mut self, x: Receiver<MemType>) -> &mut Self {
         self.input_chan = Some(x);
         self
@@ -244,6 +314,9 @@ where
             return;
         }
         self.ips_since_last_mem_edit.clear();
+        if let Some(t) = &mut self.trace {
+            t.last_writer.insert(offset, self.instruction_pointer);
+        }
         *self.memory.entry(offset).or_insert_with(Default::default) = value;
     }
     pub fn inc_ip(&mut self, offset: isize) {
@@ -272,6 +345,37 @@ where
             op.execute(self);
         }
     }
+    /// Runs until the instruction pointer reaches one of `breakpoints`, or the program halts,
+    /// wants input, or hits an instruction it can't decode. The current instruction is always
+    /// executed, so calling this again resumes from a breakpoint.
+    pub fn run_to_breakpoint(&mut self, breakpoints: &HashSet<isize>) -> StopReason {
+        let mut first = true;
+        loop {
+            let ip = self.instruction_pointer;
+            if self.state == ComputerState::HALTED {
+                return StopReason::Halted;
+            }
+            if !first && breakpoints.contains(&ip) {
+                return StopReason::Breakpoint(ip);
+            }
+            let op = match Op::try_from_mem_slice(&self.get_args(ip.try_into().unwrap())) {
+                Some(op) => op,
+                None => return StopReason::BadInstruction(ip),
+            };
+            let no_input = self.input_chan.is_none() && self.input_arc.is_none();
+            if op.op == OpCode::Input && self.fixed_input.is_empty() && no_input {
+                return StopReason::NeedsInput;
+            }
+            op.execute(self);
+            first = false;
+        }
+    }
+    pub fn instruction_pointer(&self) -> isize {
+        self.instruction_pointer
+    }
+    pub fn relative_base(&self) -> isize {
+        self.relative_base
+    }
     pub fn is_halted(&self) -> bool {
         self.state() == ComputerState::HALTED
     }
@@ -285,6 +389,46 @@ where
     pub fn state(&self) -> ComputerState {
         self.state
     }
+    pub fn initial_memory(&self) -> &[MemType] {
+        &self.initial_mem
+    }
+    pub fn memory_overlay(&self) -> &HashMap<isize, MemType> {
+        &self.memory
+    }
+    /// `(address, original, current)` for each cell that differs from the initial image.
+    pub fn changed_cells(&self) -> Vec<(isize, MemType, MemType)> {
+        let mut ans: Vec<_> = self
+            .memory
+            .iter()
+            .filter_map(|(&a, &v)| {
+                let orig = a
+                    .try_into()
+                    .ok()
+                    .and_then(|p: usize| self.initial_mem.get(p))
+                    .cloned()
+                    .unwrap_or_default();
+                if orig == v {
+                    None
+                } else {
+                    Some((a, orig, v))
+                }
+            })
+            .collect();
+        ans.sort();
+        ans
+    }
+    /// The current memory as a flat image, long enough to include every cell written.
+    pub fn memory_image(&self) -> Vec<MemType> {
+        let len = self
+            .memory
+            .keys()
+            .filter_map(|&a| a.try_into().ok())
+            .map(|a: usize| a + 1)
+            .chain(std::iter::once(self.initial_mem.len()))
+            .max()
+            .unwrap_or(0);
+        (0..len).map(|a| self.abs_load(a as isize)).collect()
+    }
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
@@ -322,6 +466,7 @@ where
         let op_count = self.op.arg_count();
         let ps = self.args;
         let mut do_ip_inc = true;
+        let (ip, rb) = (c.instruction_pointer, c.relative_base);
         match self.op {
             OpCode::Add => c.abs_store(ps[2].ptr(c), ps[0].get(c) + ps[1].get(c)),
             OpCode::Mult => c.abs_store(ps[2].ptr(c), ps[0].get(c) * ps[1].get(c)),
@@ -349,6 +494,11 @@ where
             OpCode::Output => {
                 let o = ps[0].get(c);
                 info!("OUT: {}", o);
+                let source = if ps[0].is_immediate() { None } else { Some(ps[0].ptr(c)) };
+                if let Some(t) = &mut c.trace {
+                    let writer = source.and_then(|a| t.last_writer.get(&a).cloned());
+                    t.outputs.push(OutputRecord { ip, value: o, source, writer });
+                }
                 c.output.push(o);
                 if let Some(ch) = &c.output_chan {
                     info!(target: "IO", "{} OUT <-- {}", c.name, o);
@@ -375,6 +525,14 @@ where
         if do_ip_inc {
             c.inc_ip((1 + op_count).try_into().unwrap());
         }
+        if let Some(t) = &mut c.calls {
+            let jump = if do_ip_inc || self.op == OpCode::Halt {
+                None
+            } else {
+                Some(!ps[1].is_immediate())
+            };
+            t.observe(ip, rb, c.relative_base, c.instruction_pointer, jump);
+        }
         info!("IP = {}", c.instruction_pointer);
     }
 }
@@ -0,0 +1,95 @@
+use super::compmem::CompMem;
+use super::computer::Computer;
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DeviceError<MemType> {
+    /// The program produced a different number of outputs than the protocol expects.
+    WrongOutputCount { expected: usize, got: Vec<MemType> },
+    /// An output the protocol doesn't understand.
+    UnknownCode(MemType),
+    /// The program halted without responding.
+    Halted,
+}
+
+impl<MemType: fmt::Display> fmt::Display for DeviceError<MemType> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::WrongOutputCount { expected, got } => {
+                write!(f, "expected {} outputs, got {}", expected, got.len())
+            }
+            Self::UnknownCode(c) => write!(f, "unknown response code {}", c),
+            Self::Halted => write!(f, "device halted"),
+        }
+    }
+}
+
+/// A request/response protocol spoken by an Intcode program: each command is encoded as
+/// some inputs, and the program answers each with `RESPONSE_LEN` outputs.
+pub trait Protocol<MemType: CompMem> {
+    type Command;
+    type Response;
+    const RESPONSE_LEN: usize;
+    fn encode(&self, cmd: &Self::Command) -> Vec<MemType>;
+    fn decode(&self, outputs: &[MemType]) -> Result<Self::Response, DeviceError<MemType>>;
+
+    /// Sends `cmd` to `c` and runs it until it wants more input (or halts), then decodes
+    /// what it said.
+    fn call(
+        &self,
+        c: &mut Computer<MemType>,
+        cmd: &Self::Command,
+    ) -> Result<Self::Response, DeviceError<MemType>> {
+        if c.is_halted() {
+            return Err(DeviceError::Halted);
+        }
+        c.give_input(self.encode(cmd)).run_to_input();
+        let got = c.take_output();
+        if got.len() != Self::RESPONSE_LEN {
+            return Err(if got.is_empty() && c.is_halted() {
+                DeviceError::Halted
+            } else {
+                DeviceError::WrongOutputCount {
+                    expected: Self::RESPONSE_LEN,
+                    got,
+                }
+            });
+        }
+        self.decode(&got)
+    }
+}
+
+/// A computer speaking a `Protocol`. Programs which halt after each answer (like the
+/// tractor beam drone) are restarted from their original state before the next call.
+pub struct Device<P, MemType> {
+    pub computer: Computer<MemType>,
+    pub protocol: P,
+    pristine: Computer<MemType>,
+    calls: usize,
+}
+
+impl<P, MemType> Device<P, MemType>
+where
+    MemType: CompMem,
+    P: Protocol<MemType>,
+{
+    pub fn new(computer: Computer<MemType>, protocol: P) -> Self {
+        Self {
+            pristine: computer.clone(),
+            computer,
+            protocol,
+            calls: 0,
+        }
+    }
+    pub fn call(&mut self, cmd: &P::Command) -> Result<P::Response, DeviceError<MemType>> {
+        if self.computer.is_halted() {
+            self.computer = self.pristine.clone();
+        }
+        self.calls += 1;
+        self.protocol.call(&mut self.computer, cmd)
+    }
+    /// How many commands have been sent.
+    pub fn calls(&self) -> usize {
+        self.calls
+    }
+}
@@ -0,0 +1,94 @@
+use super::compmem::CompMem;
+use super::computer::{Computer, Op};
+use std::fmt;
+
+/// A self-test which output something other than zero.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TestFailure<MemType> {
+    /// Which output (0-based) reported the failure.
+    pub output_ix: usize,
+    pub value: MemType,
+    /// The output instruction that reported it.
+    pub output_ip: isize,
+    /// The instruction that computed the reported value, if it came from memory.
+    pub failing_ip: Option<isize>,
+    /// `failing_ip` disassembled.
+    pub failing_op: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DiagnosticReport<MemType> {
+    pub passed: usize,
+    pub failures: Vec<TestFailure<MemType>>,
+    /// The final output, which isn't a self-test.
+    pub answer: Option<MemType>,
+}
+
+impl<MemType> DiagnosticReport<MemType>
+where
+    MemType: Copy,
+{
+    pub fn is_pass(&self) -> bool {
+        self.failures.is_empty() && self.answer.is_some()
+    }
+    /// The answer, if every self-test passed.
+    pub fn passed_answer(&self) -> Option<MemType> {
+        self.answer.filter(|_| self.failures.is_empty())
+    }
+}
+
+impl<MemType: fmt::Display> fmt::Display for DiagnosticReport<MemType> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} passed, {} failed", self.passed, self.failures.len())?;
+        for t in &self.failures {
+            write!(f, "output {} @{}: {}", t.output_ix, t.output_ip, t.value)?;
+            match (t.failing_ip, &t.failing_op) {
+                (Some(ip), Some(op)) => writeln!(f, " from {: >4}: {}", ip, op)?,
+                _ => writeln!(f)?,
+            }
+        }
+        match &self.answer {
+            Some(a) => write!(f, "answer: {}", a),
+            None => write!(f, "no answer"),
+        }
+    }
+}
+
+/// Runs a diagnostic program (like the day 5 TEST or day 9 BOOST programs) in test mode.
+/// Every output but the last is a self-test, which passes if it's zero. Non-zero results are
+/// traced back to the instruction which computed them.
+pub fn run_diagnostics<MemType>(c: &Computer<MemType>, system_id: MemType) -> DiagnosticReport<MemType>
+where
+    MemType: CompMem,
+{
+    let mut c = c.clone();
+    c.with_io_trace().with_input(system_id).run();
+    let records = c.io_trace().map(|t| t.outputs.clone()).unwrap_or_default();
+    let (answer, tests) = match records.split_last() {
+        Some((a, tests)) => (Some(a.value), tests),
+        None => (None, &records[..]),
+    };
+    let failures: Vec<TestFailure<MemType>> = tests
+        .iter()
+        .enumerate()
+        .filter(|(_, r)| r.value != MemType::zero())
+        .map(|(ix, r)| {
+            let failing_ip = r.writer;
+            TestFailure {
+                output_ix: ix,
+                value: r.value,
+                output_ip: r.ip,
+                failing_ip,
+                failing_op: failing_ip.and_then(|ip| {
+                    let ip = ip as usize;
+                    Op::try_from_mem_slice(&c.get_args(ip)).map(|o| o.to_string())
+                }),
+            }
+        })
+        .collect();
+    DiagnosticReport {
+        passed: tests.len() - failures.len(),
+        failures,
+        answer,
+    }
+}
@@ -0,0 +1,151 @@
+use super::compmem::CompMem;
+use super::computer::Computer;
+use itertools::Itertools;
+use num::NumCast;
+use std::fmt;
+use std::mem::size_of;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Ways of storing a program's memory image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ImageFormat {
+    /// `1,2,3`, as the puzzle inputs come.
+    Comma,
+    /// Values separated by any whitespace.
+    Whitespace,
+    /// One value per line.
+    Newline,
+    /// Little-endian integers, each as wide as the memory type.
+    Binary,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ImageError {
+    /// The value at `index` (0-based) didn't parse.
+    BadToken { index: usize, token: String },
+    /// A binary image whose length isn't a whole number of values.
+    Truncated { len: usize, width: usize },
+    /// A binary image of values wider than the 8 bytes it can hold.
+    TooWide(usize),
+    /// Text which isn't UTF-8, or a binary format used as text.
+    NotText,
+    Io(String),
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BadToken { index, token } => write!(f, "bad value '{}' at index {}", token, index),
+            Self::Truncated { len, width } => {
+                write!(f, "{} bytes isn't a whole number of {} byte values", len, width)
+            }
+            Self::TooWide(w) => write!(f, "binary images can't hold {} byte values", w),
+            Self::NotText => write!(f, "image isn't text"),
+            Self::Io(e) => write!(f, "can't access image: {}", e),
+        }
+    }
+}
+
+impl ImageFormat {
+    pub fn is_text(self) -> bool {
+        self != Self::Binary
+    }
+    /// The tokens of a text image, or `None` for a binary format.
+    fn tokens(self, s: &str) -> Option<Vec<&str>> {
+        match self {
+            Self::Comma => Some(s.trim().split(',').map(str::trim).collect()),
+            Self::Whitespace => Some(s.split_whitespace().collect()),
+            Self::Newline => Some(s.lines().map(str::trim).filter(|l| !l.is_empty()).collect()),
+            Self::Binary => None,
+        }
+    }
+    /// Parses a text image.
+    pub fn parse<MemType>(self, s: &str) -> Result<Vec<MemType>, ImageError>
+    where
+        MemType: CompMem + FromStr,
+    {
+        self.tokens(s)
+            .ok_or(ImageError::NotText)?
+            .into_iter()
+            .enumerate()
+            .map(|(index, t)| {
+                t.parse().map_err(|_| ImageError::BadToken {
+                    index,
+                    token: t.to_string(),
+                })
+            })
+            .collect()
+    }
+    pub fn decode<MemType>(self, bytes: &[u8]) -> Result<Vec<MemType>, ImageError>
+    where
+        MemType: CompMem + FromStr,
+    {
+        if self.is_text() {
+            return self.parse(std::str::from_utf8(bytes).map_err(|_| ImageError::NotText)?);
+        }
+        let width = binary_width::<MemType>()?;
+        if !bytes.len().is_multiple_of(width) {
+            return Err(ImageError::Truncated {
+                len: bytes.len(),
+                width,
+            });
+        }
+        Ok(bytes
+            .chunks(width)
+            .map(|c| {
+                //sign extend to 8 bytes; that always fits back into the memory type.
+                let fill = if c[width - 1] & 0x80 == 0 { 0 } else { 0xff };
+                let mut b = [fill; 8];
+                b[..width].copy_from_slice(c);
+                NumCast::from(i64::from_le_bytes(b)).unwrap()
+            })
+            .collect())
+    }
+    pub fn encode<MemType: CompMem>(self, mem: &[MemType]) -> Result<Vec<u8>, ImageError> {
+        let sep = match self {
+            Self::Comma => ",",
+            Self::Whitespace => " ",
+            Self::Newline => "\n",
+            Self::Binary => {
+                let width = binary_width::<MemType>()?;
+                return Ok(mem
+                    .iter()
+                    .flat_map(|&m| {
+                        //fits, as the memory type is no wider than an i64.
+                        let v: i64 = NumCast::from(m).unwrap();
+                        v.to_le_bytes()[..width].to_vec()
+                    })
+                    .collect());
+            }
+        };
+        Ok(format!("{}\n", mem.iter().join(sep)).into_bytes())
+    }
+    pub fn load<MemType, P>(self, path: P) -> Result<Computer<MemType>, ImageError>
+    where
+        MemType: CompMem + FromStr,
+        P: AsRef<Path>,
+    {
+        let bytes = std::fs::read(path).map_err(|e| ImageError::Io(e.to_string()))?;
+        Ok(Computer::new(&self.decode(&bytes)?))
+    }
+    /// Saves the current memory of `c`, including any changes it's made.
+    pub fn save<MemType, P>(self, c: &Computer<MemType>, path: P) -> Result<(), ImageError>
+    where
+        MemType: CompMem,
+        P: AsRef<Path>,
+    {
+        let bytes = self.encode(&c.memory_image())?;
+        std::fs::write(path, bytes).map_err(|e| ImageError::Io(e.to_string()))
+    }
+}
+
+/// How many bytes each value of a binary image takes, if they fit in the 8 bytes it allows.
+fn binary_width<MemType>() -> Result<usize, ImageError> {
+    let width = size_of::<MemType>();
+    if width > size_of::<i64>() {
+        Err(ImageError::TooWide(width))
+    } else {
+        Ok(width)
+    }
+}
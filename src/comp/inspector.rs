@@ -0,0 +1,145 @@
+use super::compmem::CompMem;
+use super::computer::Computer;
+use std::collections::HashMap;
+use std::convert::TryInto;
+
+/// The memory of a computer at some point in its run: every cell of the initial image, plus
+/// any the program has written beyond it.
+#[derive(Clone, Debug)]
+pub struct Snapshot<MemType> {
+    pub label: String,
+    pub cells: HashMap<isize, MemType>,
+}
+
+impl<MemType> Snapshot<MemType>
+where
+    MemType: CompMem,
+{
+    pub fn take(c: &Computer<MemType>, label: &str) -> Self {
+        let image_len: isize = c.initial_memory().len().try_into().unwrap();
+        let cells = (0..image_len)
+            .chain(c.memory_overlay().keys().cloned())
+            .map(|a| (a, c.abs_load(a)))
+            .collect();
+        Self {
+            label: label.to_string(),
+            cells,
+        }
+    }
+    pub fn get(&self, addr: isize) -> MemType {
+        self.cells.get(&addr).cloned().unwrap_or_default()
+    }
+    /// `(address, before, after)` for every cell that differs in `later`.
+    pub fn diff(&self, later: &Self) -> Vec<(isize, MemType, MemType)> {
+        let mut addrs: Vec<isize> = self.cells.keys().chain(later.cells.keys()).cloned().collect();
+        addrs.sort();
+        addrs.dedup();
+        addrs
+            .into_iter()
+            .filter_map(|a| {
+                let (x, y) = (self.get(a), later.get(a));
+                if x == y {
+                    None
+                } else {
+                    Some((a, x, y))
+                }
+            })
+            .collect()
+    }
+}
+
+/// Collects snapshots of a running program to find which cells hold which variables, the
+/// way you'd hunt for the lives counter in a game. Cells found can be given names, and read
+/// or written by name.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryInspector<MemType> {
+    snapshots: Vec<Snapshot<MemType>>,
+    names: HashMap<String, isize>,
+}
+
+impl<MemType> MemoryInspector<MemType>
+where
+    MemType: CompMem,
+{
+    pub fn new() -> Self {
+        Self {
+            snapshots: Vec::new(),
+            names: HashMap::new(),
+        }
+    }
+    /// Takes a snapshot of `c`, returning its index.
+    pub fn capture(&mut self, c: &Computer<MemType>, label: &str) -> usize {
+        self.snapshots.push(Snapshot::take(c, label));
+        self.snapshots.len() - 1
+    }
+    pub fn snapshots(&self) -> &[Snapshot<MemType>] {
+        &self.snapshots
+    }
+    pub fn clear_snapshots(&mut self) -> &mut Self {
+        self.snapshots.clear();
+        self
+    }
+    pub fn diff(&self, before: usize, after: usize) -> Vec<(isize, MemType, MemType)> {
+        self.snapshots[before].diff(&self.snapshots[after])
+    }
+    /// Addresses whose values across all the snapshots satisfy `pred`.
+    pub fn search_by<F>(&self, pred: F) -> Vec<isize>
+    where
+        F: Fn(&[MemType]) -> bool,
+    {
+        let mut addrs: Vec<isize> = self
+            .snapshots
+            .iter()
+            .flat_map(|s| s.cells.keys())
+            .cloned()
+            .collect();
+        addrs.sort();
+        addrs.dedup();
+        addrs
+            .into_iter()
+            .filter(|&a| {
+                let history: Vec<MemType> = self.snapshots.iter().map(|s| s.get(a)).collect();
+                pred(&history)
+            })
+            .collect()
+    }
+    /// Addresses which held `values[i]` in snapshot `i`, for every snapshot.
+    pub fn search(&self, values: &[MemType]) -> Vec<isize> {
+        assert_eq!(values.len(), self.snapshots.len(), "One value per snapshot");
+        self.search_by(|h| h == values)
+    }
+    /// Addresses whose value changed between every pair of consecutive snapshots.
+    pub fn search_always_changing(&self) -> Vec<isize> {
+        self.search_by(|h| h.windows(2).all(|w| w[0] != w[1]))
+    }
+    pub fn name(&mut self, name: &str, addr: isize) -> &mut Self {
+        self.names.insert(name.to_string(), addr);
+        self
+    }
+    /// Searches for `values` as in `search`, and names the cell if exactly one matches.
+    pub fn find_and_name(&mut self, name: &str, values: &[MemType]) -> Option<isize> {
+        match self.search(values)[..] {
+            [addr] => {
+                self.name(name, addr);
+                Some(addr)
+            }
+            _ => None,
+        }
+    }
+    pub fn address_of(&self, name: &str) -> Option<isize> {
+        self.names.get(name).cloned()
+    }
+    pub fn names(&self) -> &HashMap<String, isize> {
+        &self.names
+    }
+    pub fn read(&self, c: &Computer<MemType>, name: &str) -> Option<MemType> {
+        self.address_of(name).map(|a| c.abs_load(a))
+    }
+    /// Stores `value` in the named cell, returning its address, or `None` if there's no
+    /// cell of that name.
+    pub fn write(&self, c: &mut Computer<MemType>, name: &str, value: MemType) -> Option<isize> {
+        let addr = self.address_of(name)?;
+        c.abs_store(addr, value);
+        Some(addr)
+    }
+}
@@ -1,10 +1,67 @@
+mod bridge;
+mod callstack;
+mod compiler;
 mod compmem;
 mod computer;
+mod device;
+mod diagnostics;
 mod enums;
+mod image;
+mod inspector;
 mod oparg;
 mod opcode;
+mod patch;
+mod search;
 
 #[cfg(test)]
 mod comp_tests;
 
-pub use computer::Computer;
+pub use bridge::{BridgeMode, Client, Reply, Server};
+pub use callstack::{Backtrace, CallTrace, Frame, FrameDump};
+pub use compiler::{compile, CompileError};
+pub use computer::{Computer, IoTrace, OutputRecord, StopReason};
+pub use device::{Device, DeviceError, Protocol};
+pub use diagnostics::{run_diagnostics, DiagnosticReport, TestFailure};
+pub use image::{ImageError, ImageFormat};
+pub use intcode_macro::intcode_image;
+pub use inspector::{MemoryInspector, Snapshot};
+pub use patch::{Patch, PatchError, PatchSet};
+pub use search::{par_find, par_map};
+
+/// A program's memory image as a `&'static [MemType]`, checked at compile time. Takes a
+/// string literal of numbers or assembly; see `intcode_image` for the syntax.
+///
+/// ```
+/// let prog: &[i32] = aoc_2019_rs::intcode!("in @5; out @5; hlt");
+/// assert_eq!(prog, &[3, 5, 4, 5, 99]);
+/// ```
+///
+/// Malformed and empty programs don't compile, nor do values which don't fit in an `i64`,
+/// whatever the element type:
+///
+/// ```compile_fail
+/// let prog: &[i32] = aoc_2019_rs::intcode!("1,2,x");
+/// ```
+/// ```compile_fail
+/// let prog: &[i32] = aoc_2019_rs::intcode!("add 1, 2, 3");
+/// ```
+/// ```compile_fail
+/// let prog: &[i32] = aoc_2019_rs::intcode!(" // nothing\n");
+/// ```
+/// ```compile_fail
+/// let prog: &[i128] = aoc_2019_rs::intcode!("1,2,9223372036854775808");
+/// ```
+#[macro_export]
+macro_rules! intcode {
+    ($s:literal) => {
+        $crate::comp::intcode_image!($s)
+    };
+}
+
+/// As `intcode!`, but makes a `Computer` of the image.
+#[macro_export]
+macro_rules! intcode_computer {
+    ($s:literal) => {
+        $crate::comp::Computer::new($crate::comp::intcode_image!($s))
+    };
+}
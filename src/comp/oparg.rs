@@ -20,6 +20,9 @@ where
             Self(i, ParameterMode::RELATIVE) => c.rel_load(i.as_isize()),
         }
     }
+    pub fn is_immediate(self) -> bool {
+        self.1 == ParameterMode::IMMEDIATE
+    }
     pub fn ptr(self, c: &Computer<MemType>) -> isize {
         match self {
             Self(_, ParameterMode::IMMEDIATE) => panic!("Write instruction in immediate mode."),
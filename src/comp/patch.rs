@@ -0,0 +1,159 @@
+use super::compmem::CompMem;
+use super::computer::Computer;
+use itertools::Itertools;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+/// A named set of memory writes to make to a program before running it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Patch<MemType> {
+    pub name: String,
+    pub description: String,
+    pub writes: BTreeMap<isize, MemType>,
+}
+
+impl<MemType> Patch<MemType>
+where
+    MemType: CompMem,
+{
+    pub fn new(name: &str, description: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            description: description.to_string(),
+            writes: BTreeMap::new(),
+        }
+    }
+    pub fn with(mut self, addr: isize, value: MemType) -> Self {
+        self.writes.insert(addr, value);
+        self
+    }
+    /// A patch recreating every change `c` has made to its initial memory image.
+    pub fn from_changes(name: &str, description: &str, c: &Computer<MemType>) -> Self {
+        c.changed_cells()
+            .into_iter()
+            .fold(Self::new(name, description), |p, (a, _, v)| p.with(a, v))
+    }
+    pub fn apply<'a>(&self, c: &'a mut Computer<MemType>) -> &'a mut Computer<MemType> {
+        for (&a, &v) in &self.writes {
+            c.abs_store(a, v);
+        }
+        c
+    }
+    /// This patch followed by `other`; where both write the same address, `other` wins.
+    pub fn then(&self, other: &Self) -> Self {
+        let mut ans = self.clone();
+        ans.name = format!("{}+{}", self.name, other.name);
+        ans.description = format!("{}; {}", self.description, other.description);
+        ans.writes.extend(other.writes.iter().map(|(&a, &v)| (a, v)));
+        ans
+    }
+}
+
+impl<MemType> fmt::Display for Patch<MemType>
+where
+    MemType: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "[{}] {}", self.name, self.description)?;
+        for (a, v) in &self.writes {
+            writeln!(f, "{} = {}", a, v)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum PatchError {
+    Io(String),
+    /// A line (1-based) that isn't a header, a write, or a comment.
+    Parse(usize, String),
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "can't read patch file: {}", e),
+            Self::Parse(l, s) => write!(f, "line {}: bad patch line '{}'", l, s),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PatchSet<MemType> {
+    pub patches: Vec<Patch<MemType>>,
+}
+
+impl<MemType> PatchSet<MemType>
+where
+    MemType: CompMem,
+{
+    pub fn get(&self, name: &str) -> Option<&Patch<MemType>> {
+        self.patches.iter().find(|p| p.name == name)
+    }
+    /// All the patches applied in order, as one.
+    pub fn combined(&self, name: &str) -> Patch<MemType> {
+        let desc = self.patches.iter().map(|p| &p.description).join("; ");
+        let mut ans = Patch::new(name, &desc);
+        for p in &self.patches {
+            ans.writes.extend(p.writes.iter().map(|(&a, &v)| (a, v)));
+        }
+        ans
+    }
+}
+
+impl<MemType> PatchSet<MemType>
+where
+    MemType: CompMem + FromStr,
+{
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, PatchError> {
+        std::fs::read_to_string(path)
+            .map_err(|e| PatchError::Io(e.to_string()))?
+            .parse()
+    }
+}
+
+/// Patch sets as text: each patch is a `[name] description` header followed by
+/// `address = value` lines. Blank lines and lines starting with `#` are ignored.
+impl<MemType> FromStr for PatchSet<MemType>
+where
+    MemType: CompMem + FromStr,
+{
+    type Err = PatchError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut patches: Vec<Patch<MemType>> = Vec::new();
+        for (ix, line) in s.lines().map(str::trim).enumerate() {
+            let bad = || PatchError::Parse(ix + 1, line.to_string());
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if line.starts_with('[') {
+                let close = line.find(']').ok_or_else(bad)?;
+                patches.push(Patch::new(&line[1..close], line[close + 1..].trim()));
+                continue;
+            }
+            let mut parts = line.splitn(2, '=').map(str::trim);
+            let addr = parts.next().and_then(|a| a.parse().ok()).ok_or_else(bad)?;
+            let value = parts.next().and_then(|v| v.parse().ok()).ok_or_else(bad)?;
+            let p = patches.last_mut().ok_or_else(bad)?;
+            p.writes.insert(addr, value);
+        }
+        Ok(Self { patches })
+    }
+}
+
+impl<MemType> fmt::Display for PatchSet<MemType>
+where
+    MemType: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (ix, p) in self.patches.iter().enumerate() {
+            if ix > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", p)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,59 @@
+use super::compmem::CompMem;
+use super::computer::Computer;
+use rayon::prelude::*;
+
+/// Tries each parameter in `space` on a copy of `template`, in parallel. Each thread keeps
+/// one working copy, reset from the template before each parameter. `setup` prepares it
+/// (patches, inputs) and `eval` runs it, returning `Some` for a match.
+/// Returns the earliest parameter (in `space` order) that matches, with its result.
+pub fn par_find<MemType, I, P, R, S, E>(
+    template: &Computer<MemType>,
+    space: I,
+    setup: S,
+    eval: E,
+) -> Option<(P, R)>
+where
+    MemType: CompMem + Send + Sync,
+    I: IntoParallelIterator<Item = P>,
+    P: Send,
+    R: Send,
+    S: Fn(&mut Computer<MemType>, &P) + Sync,
+    E: Fn(&mut Computer<MemType>, &P) -> Option<R> + Sync,
+{
+    space
+        .into_par_iter()
+        .map_with((template.clone(), template.clone()), |(t, c), p| {
+            c.clone_from(t);
+            setup(c, &p);
+            eval(c, &p).map(|r| (p, r))
+        })
+        .find_first(Option::is_some)
+        .flatten()
+}
+
+/// As `par_find`, but with no early exit: every parameter's result is returned, in `space`
+/// order.
+pub fn par_map<MemType, I, P, R, S, E>(
+    template: &Computer<MemType>,
+    space: I,
+    setup: S,
+    eval: E,
+) -> Vec<(P, R)>
+where
+    MemType: CompMem + Send + Sync,
+    I: IntoParallelIterator<Item = P>,
+    P: Send,
+    R: Send,
+    S: Fn(&mut Computer<MemType>, &P) + Sync,
+    E: Fn(&mut Computer<MemType>, &P) -> R + Sync,
+{
+    space
+        .into_par_iter()
+        .map_with((template.clone(), template.clone()), |(t, c), p| {
+            c.clone_from(t);
+            setup(c, &p);
+            let r = eval(c, &p);
+            (p, r)
+        })
+        .collect()
+}
@@ -1,10 +1,14 @@
-use crate::comp::Computer;
+use crate::comp::{par_find, Computer, Patch};
 use std::str::FromStr;
 
+pub fn noun_verb(noun: i32, verb: i32) -> Patch<i32> {
+    Patch::new("noun_verb", "Set the gravity assist program's inputs")
+        .with(1, noun)
+        .with(2, verb)
+}
+
 pub fn run_with_args(c: &mut Computer, noun: i32, verb: i32) -> i32 {
-    c.abs_store(1, noun);
-    c.abs_store(2, verb);
-    c.run().abs_load(0)
+    noun_verb(noun, verb).apply(c).run().abs_load(0)
 }
 
 #[aoc(day2, part1)]
@@ -13,8 +17,8 @@ pub fn p1(input: &str) -> i32 {
     run_with_args(&mut c, 12, 2)
 }
 
-#[aoc(day2, part2)]
-pub fn p2(input: &str) -> i32 {
+#[aoc(day2, part2, serial)]
+pub fn p2_serial(input: &str) -> i32 {
     let mut c = Computer::from_str(input).unwrap();
     let (n, v) = (0..100)
         .flat_map(move |n| (0..100).map(move |v| (n, v)))
@@ -22,3 +26,19 @@ pub fn p2(input: &str) -> i32 {
         .unwrap();
     100 * n + v
 }
+
+#[aoc(day2, part2)]
+pub fn p2(input: &str) -> i32 {
+    let c = Computer::from_str(input).unwrap();
+    let pairs: Vec<(i32, i32)> = (0..100).flat_map(|n| (0..100).map(move |v| (n, v))).collect();
+    let ((n, v), ()) = par_find(
+        &c,
+        pairs,
+        |c, &(n, v)| {
+            noun_verb(n, v).apply(c);
+        },
+        |c, _| Some(()).filter(|_| c.run().abs_load(0) == 19_690_720),
+    )
+    .unwrap();
+    100 * n + v
+}
@@ -0,0 +1,221 @@
+use crate::comp::Computer;
+use itertools::Itertools;
+use std::collections::{HashMap, HashSet};
+use std::iter::once;
+
+/// Which phase settings to try.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PhaseSpace {
+    /// Each value used by at most one amplifier, as in the puzzle.
+    Permutations(Vec<isize>),
+    /// Any value for any amplifier.
+    Product(Vec<isize>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Goal {
+    Max,
+    Min,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Best {
+    pub phases: Vec<isize>,
+    pub signal: isize,
+    /// How many amplifier programs were run, after memoization.
+    pub amp_runs: usize,
+}
+
+/// Amplifier outputs, by phase and the inputs that followed it.
+type Memo = HashMap<(isize, Vec<isize>), Vec<isize>>;
+
+/// A set of amplifiers, all running the same program, wired together. Each amplifier reads
+/// its phase setting, then any signals fed to it directly, then its wired inputs.
+///
+/// Acyclic rigs run each amplifier once, in topological order, on all the outputs of its
+/// sources, in wiring order. Rigs with feedback loops run the amplifiers in turn until they
+/// all halt or stall. Either way, the result is the last output of the output amplifier.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rig {
+    amps: usize,
+    wires: Vec<(usize, usize)>,
+    inputs: Vec<(usize, isize)>,
+    output: usize,
+}
+
+impl Rig {
+    /// Amplifiers with no connections; the last one's output is the result.
+    pub fn new(amps: usize) -> Self {
+        assert!(amps > 0, "A rig needs at least one amplifier");
+        Self {
+            amps,
+            wires: Vec::new(),
+            inputs: Vec::new(),
+            output: amps - 1,
+        }
+    }
+    /// Each amplifier feeding the next, with 0 fed to the first.
+    pub fn chain(amps: usize) -> Self {
+        (1..amps).fold(Self::new(amps).input(0, 0), |r, a| r.wire(a - 1, a))
+    }
+    /// A chain with the last amplifier feeding back to the first.
+    pub fn ring(amps: usize) -> Self {
+        Self::chain(amps).wire(amps - 1, 0)
+    }
+    fn check(&self, amp: usize) {
+        assert!(amp < self.amps, "No amplifier {} in a rig of {}", amp, self.amps);
+    }
+    /// Sends `from`'s output to `to`.
+    pub fn wire(mut self, from: usize, to: usize) -> Self {
+        self.check(from);
+        self.check(to);
+        self.wires.push((from, to));
+        self
+    }
+    /// Feeds `value` to `amp` after its phase.
+    pub fn input(mut self, amp: usize, value: isize) -> Self {
+        self.check(amp);
+        self.inputs.push((amp, value));
+        self
+    }
+    /// Makes `amp`'s output the result.
+    pub fn output(mut self, amp: usize) -> Self {
+        self.check(amp);
+        self.output = amp;
+        self
+    }
+    fn sources(&self, amp: usize) -> impl Iterator<Item = usize> + '_ {
+        self.wires.iter().filter(move |w| w.1 == amp).map(|w| w.0)
+    }
+    /// The amplifiers in an order where each comes after its sources, if there's no loop.
+    pub fn topological_order(&self) -> Option<Vec<usize>> {
+        let mut order = Vec::new();
+        let mut done = HashSet::new();
+        while order.len() < self.amps {
+            let next = (0..self.amps)
+                .find(|a| !done.contains(a) && self.sources(*a).all(|s| done.contains(&s)))?;
+            done.insert(next);
+            order.push(next);
+        }
+        Some(order)
+    }
+    pub fn is_acyclic(&self) -> bool {
+        self.topological_order().is_some()
+    }
+    fn direct_inputs(&self, amp: usize) -> impl Iterator<Item = isize> + '_ {
+        self.inputs.iter().filter(move |i| i.0 == amp).map(|i| i.1)
+    }
+    fn run_acyclic(
+        &self,
+        program: &Computer<isize>,
+        phases: &[isize],
+        memo: &mut Memo,
+    ) -> Option<isize> {
+        let mut outputs = vec![Vec::new(); self.amps];
+        for a in self.topological_order()? {
+            let mut input: Vec<isize> = self.direct_inputs(a).collect();
+            for s in self.sources(a) {
+                input.extend_from_slice(&outputs[s]);
+            }
+            outputs[a] = memo
+                .entry((phases[a], input))
+                .or_insert_with_key(|(p, i)| {
+                    let mut c = program.clone();
+                    c.give_input(once(*p).chain(i.iter().cloned()).collect());
+                    c.run_to_breakpoint(&HashSet::new());
+                    c.take_output()
+                })
+                .clone();
+        }
+        outputs[self.output].last().cloned()
+    }
+    /// Returns the result and how many times amplifiers were run.
+    fn run_feedback(&self, program: &Computer<isize>, phases: &[isize]) -> (Option<isize>, usize) {
+        let mut comps = vec![program.clone(); self.amps];
+        let mut queues: Vec<Vec<isize>> = (0..self.amps)
+            .map(|a| once(phases[a]).chain(self.direct_inputs(a)).collect())
+            .collect();
+        let (mut last, mut runs) = (None, 0);
+        loop {
+            let mut progress = false;
+            for a in 0..self.amps {
+                if comps[a].is_halted() || queues[a].is_empty() {
+                    continue;
+                }
+                progress = true;
+                runs += 1;
+                comps[a].give_input(std::mem::take(&mut queues[a]));
+                comps[a].run_to_breakpoint(&HashSet::new());
+                let out = comps[a].take_output();
+                for &(_, to) in self.wires.iter().filter(|w| w.0 == a) {
+                    queues[to].extend_from_slice(&out);
+                }
+                if a == self.output && !out.is_empty() {
+                    last = out.last().cloned();
+                }
+            }
+            if !progress {
+                return (last, runs);
+            }
+        }
+    }
+    /// Runs `program` on each amplifier with the given phases.
+    pub fn run(&self, program: &Computer<isize>, phases: &[isize]) -> Option<isize> {
+        assert_eq!(phases.len(), self.amps, "One phase per amplifier");
+        if self.is_acyclic() {
+            self.run_acyclic(program, phases, &mut Memo::new())
+        } else {
+            self.run_feedback(program, phases).0
+        }
+    }
+    /// Finds the phases giving the best result. Runs of acyclic rigs are memoized, so
+    /// amplifiers whose phase and inputs have been seen before aren't run again.
+    pub fn optimize(
+        &self,
+        program: &Computer<isize>,
+        space: &PhaseSpace,
+        goal: Goal,
+    ) -> Option<Best> {
+        let candidates: Vec<Vec<isize>> = match space {
+            PhaseSpace::Permutations(v) => v.iter().cloned().permutations(self.amps).collect(),
+            PhaseSpace::Product(v) => (0..self.amps)
+                .map(|_| v.iter().cloned())
+                .multi_cartesian_product()
+                .collect(),
+        };
+        let acyclic = self.is_acyclic();
+        let mut memo = Memo::new();
+        let mut runs = 0;
+        let scored = candidates.into_iter().filter_map(|p| {
+            let signal = if acyclic {
+                self.run_acyclic(program, &p, &mut memo)
+            } else {
+                let (s, r) = self.run_feedback(program, &p);
+                runs += r;
+                s
+            };
+            signal.map(|s| (p, s))
+        });
+        let best = match goal {
+            Goal::Max => scored.max_by_key(|x| x.1),
+            Goal::Min => scored.min_by_key(|x| x.1),
+        };
+        best.map(|(phases, signal)| Best {
+            phases,
+            signal,
+            amp_runs: if acyclic { memo.len() } else { runs },
+        })
+    }
+}
+
+#[test]
+#[should_panic(expected = "A rig needs at least one amplifier")]
+pub fn empty_rig() {
+    Rig::ring(0);
+}
+
+#[test]
+#[should_panic(expected = "No amplifier 3 in a rig of 3")]
+pub fn bad_wire() {
+    Rig::chain(3).wire(3, 0);
+}
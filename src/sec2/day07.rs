@@ -1,74 +1,63 @@
+use super::amplifiers::{Goal, PhaseSpace, Rig};
 use crate::comp::Computer;
-use itertools::Itertools;
+#[cfg(test)]
+use crate::intcode_computer;
 use std::str::FromStr;
-use std::sync::{Arc, Mutex};
-use std::thread;
+
+fn best_signal(c: &Computer<isize>, rig: &Rig, phases: &[isize]) -> isize {
+    rig.optimize(c, &PhaseSpace::Permutations(phases.to_vec()), Goal::Max)
+        .unwrap()
+        .signal
+}
 
 #[aoc(day7, part1)]
 pub fn p1(input: &str) -> isize {
-    (0..5)
-        .permutations(5)
-        .map(|x| run_comp_loop(input, &x))
-        .max()
-        .unwrap()
+    best_signal(&Computer::from_str(input).unwrap(), &Rig::chain(5), &[0, 1, 2, 3, 4])
 }
 
 #[test]
 pub fn p1_tests() {
-    let e0 = "3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0";
-    assert_eq!(p1(e0), 43210);
+    let e0 = intcode_computer!("3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0");
+    assert_eq!(best_signal(&e0, &Rig::chain(5), &[0, 1, 2, 3, 4]), 43210);
 }
 
 #[aoc(day7, part2)]
 pub fn p2(input: &str) -> isize {
-    (5..10)
-        .permutations(5)
-        .map(|x| run_comp_loop(input, &x))
-        .max()
-        .unwrap()
-}
-fn run_comp_loop(input: &str, a: &[isize]) -> isize {
-    let c_count = a.len();
-    let comps: Vec<_> =
-        std::iter::repeat_with(|| Arc::new(Mutex::new(Computer::from_str(input).unwrap())))
-            .take(c_count)
-            .collect();
-    for (ix, v) in a.iter().enumerate() {
-        let mut this_comp = comps[ix].lock().unwrap();
-        let mut prev_comp = comps[(ix + c_count - 1) % c_count].lock().unwrap();
-        let mut input = vec![*v];
-        if ix == 0 {
-            input.push(0)
-        }
-        this_comp
-            .with_name(format!("C-{}-{}", ix, v))
-            .connect_output_from(&mut prev_comp, &input);
-    }
-    let ts: Vec<_> = comps
-        .iter()
-        .map(|c| {
-            let cc = c.clone();
-            thread::spawn(move || {
-                let mut m = cc.lock().unwrap();
-                m.run();
-            })
-        })
-        .collect();
-    for t in ts {
-        t.join().unwrap();
-    }
-    let last_comp = comps.last().unwrap().lock().unwrap();
-    last_comp.get_last_output()
+    best_signal(&Computer::from_str(input).unwrap(), &Rig::ring(5), &[5, 6, 7, 8, 9])
 }
 
 #[test]
 pub fn p2_tests() {
-    //crate::utils::log::enable_logging();
-    let e0 =
-        "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5";
-    assert_eq!(run_comp_loop(e0, &vec![9, 8, 7, 6, 5]), 139629729);
-    assert_eq!(p2(e0), 139629729);
-    let e1= "3,52,1001,52,-5,52,3,53,1,52,56,54,1007,54,5,55,1005,55,26,1001,54,-5,54,1105,1,12,1,53,54,53,1008,54,0,55,1001,55,1,55,2,53,55,53,4,53,1001,56,-1,56,1005,56,6,99,0,0,0,0,10";
-    assert_eq!(run_comp_loop(e1, &vec![9, 7, 8, 5, 6]), 18216);
-    assert_eq!(p2(e1), 18216);
+    let run_ring = |c, p: &[isize]| Rig::ring(5).run(c, p).unwrap();
+    let best_ring = |c| best_signal(c, &Rig::ring(5), &[5, 6, 7, 8, 9]);
+    let e0 = intcode_computer!(
+        "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5"
+    );
+    assert_eq!(run_ring(&e0, &[9, 8, 7, 6, 5]), 139629729);
+    assert_eq!(best_ring(&e0), 139629729);
+    let e1 = intcode_computer!("3,52,1001,52,-5,52,3,53,1,52,56,54,1007,54,5,55,1005,55,26,1001,54,-5,54,1105,1,12,1,53,54,53,1008,54,0,55,1001,55,1,55,2,53,55,53,4,53,1001,56,-1,56,1005,56,6,99,0,0,0,0,10");
+    assert_eq!(run_ring(&e1, &[9, 7, 8, 5, 6]), 18216);
+    assert_eq!(best_ring(&e1), 18216);
+}
+
+#[test]
+pub fn rig_tests() {
+    use crate::comp::compile;
+    use super::amplifiers::Best;
+    //reads its phase, then outputs 10 * x + phase for each x it reads.
+    let amp: Computer<isize> =
+        Computer::new(&compile("fn main() { var p = input(); while (1) { output(input() * 10 + p); } }").unwrap());
+    //0 feeds 1 and 2, which both feed 3.
+    let diamond = Rig::new(4).input(0, 0).wire(0, 1).wire(0, 2).wire(1, 3).wire(2, 3);
+    assert!(diamond.is_acyclic());
+    assert_eq!(diamond.run(&amp, &[4, 1, 3, 2]), Some(432));
+    let space = PhaseSpace::Permutations(vec![1, 2, 3, 4]);
+    let best = diamond.optimize(&amp, &space, Goal::Max);
+    //4 first amps, 12 distinct second amps (shared between 1 and 2), and 24 last amps.
+    assert_eq!(best, Some(Best { phases: vec![4, 1, 3, 2], signal: 432, amp_runs: 40 }));
+    assert_eq!(diamond.optimize(&amp, &space, Goal::Min).unwrap().signal, 123);
+    let any = diamond.clone().output(1).optimize(&amp, &PhaseSpace::Product(vec![0, 9]), Goal::Max);
+    assert_eq!(any.map(|b| b.signal), Some(99));
+    assert!(!Rig::ring(3).is_acyclic());
+    assert_eq!(Rig::ring(3).topological_order(), None);
 }
@@ -1,79 +1,316 @@
+use crate::utils::ocr::{read_grid, Reading};
+use crate::utils::points::Point;
 use counter::Counter;
 use itertools::Itertools;
 use std::cmp::max;
+use std::collections::HashMap;
+use std::fmt;
 use transpose::transpose_inplace;
 
 const WIDTH: usize = 25;
 const HEIGHT: usize = 6;
-const IM_SIZE: usize = WIDTH * HEIGHT;
 
-fn conv(c: char) -> char {
-    match c {
-        '1' => '█',
-        '0' => ' ',
-        '2' => 'X',
-        _ => panic!("Unknown pixel"),
+pub const BLACK: u8 = 0;
+pub const WHITE: u8 = 1;
+pub const TRANSPARENT: u8 = 2;
+
+fn conv(d: u8) -> char {
+    match d {
+        WHITE => '█',
+        BLACK => ' ',
+        TRANSPARENT => 'X',
+        d => char::from(b'0' + d),
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SifError {
+    /// A character which isn't a digit, at byte `index` in the input as given.
+    BadPixel { index: usize, c: char },
+    /// The input isn't a whole number of layers.
+    BadSize { len: usize, width: usize, height: usize },
+}
+
+impl fmt::Display for SifError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BadPixel { index, c } => write!(f, "bad pixel '{}' at index {}", c, index),
+            Self::BadSize { len, width, height } => {
+                write!(f, "{} pixels isn't a whole number of {}x{} layers", len, width, height)
+            }
+        }
+    }
+}
+
+fn count_digit(layer: &[u8], digit: u8) -> usize {
+    layer.iter().filter(|&&d| d == digit).count()
+}
+
+/// Ways of working out which pixel in each stack shows through.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Decoder {
+    /// Paints each layer onto the transparent parts of the image so far.
+    ForLoop,
+    /// Transposes the layers so each pixel's stack is contiguous.
+    Transpose,
+    /// Steps through the layers for each pixel.
+    Steps,
+}
+
+/// An image in the Space Image Format: layers of digits, the first layer on top.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SifImage {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+}
+
+impl SifImage {
+    pub fn parse(s: &str, width: usize, height: usize) -> Result<Self, SifError> {
+        let trimmed = s.trim_start();
+        let skipped = s.len() - trimmed.len();
+        let pixels = trimmed
+            .trim_end()
+            .char_indices()
+            .map(|(index, c)| {
+                c.to_digit(10).map(|d| d as u8).ok_or(SifError::BadPixel {
+                    index: skipped + index,
+                    c,
+                })
+            })
+            .collect::<Result<Vec<u8>, _>>()?;
+        Self::from_pixels(pixels, width, height)
+    }
+    /// An image from its layers, each `width * height` pixels long.
+    pub fn from_layers(layers: &[Vec<u8>], width: usize, height: usize) -> Result<Self, SifError> {
+        Self::from_pixels(layers.concat(), width, height)
+    }
+    fn from_pixels(pixels: Vec<u8>, width: usize, height: usize) -> Result<Self, SifError> {
+        let size = width * height;
+        if size == 0 || pixels.is_empty() || !pixels.len().is_multiple_of(size) {
+            return Err(SifError::BadSize {
+                len: pixels.len(),
+                width,
+                height,
+            });
+        }
+        Ok(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+    pub fn width(&self) -> usize {
+        self.width
+    }
+    pub fn height(&self) -> usize {
+        self.height
+    }
+    fn layer_size(&self) -> usize {
+        self.width * self.height
+    }
+    pub fn layer_count(&self) -> usize {
+        self.pixels.len() / self.layer_size()
+    }
+    pub fn layers(&self) -> impl Iterator<Item = &[u8]> {
+        self.pixels.chunks(self.layer_size())
+    }
+    pub fn layer(&self, ix: usize) -> Option<&[u8]> {
+        self.layers().nth(ix)
+    }
+    /// How many of each digit are in a layer, if there is one at `ix`.
+    pub fn digit_counts(&self, ix: usize) -> Option<HashMap<u8, usize>> {
+        self.layer(ix).map(|l| Counter::init(l.iter().cloned()).into_map())
     }
+    pub fn count(&self, ix: usize, digit: u8) -> Option<usize> {
+        self.layer(ix).map(|l| count_digit(l, digit))
+    }
+    /// The first layer with the fewest of `digit`.
+    pub fn layer_with_fewest(&self, digit: u8) -> usize {
+        self.layers()
+            .enumerate()
+            .min_by_key(|(_, l)| count_digit(l, digit))
+            .map(|(ix, _)| ix)
+            .unwrap()
+    }
+    /// The number of 1s times the number of 2s, on the layer with fewest 0s.
+    pub fn checksum(&self) -> usize {
+        let l = self.layers().min_by_key(|l| count_digit(l, 0)).unwrap();
+        count_digit(l, 1) * count_digit(l, 2)
+    }
+    /// The digits, layer after layer.
+    pub fn encode(&self) -> String {
+        self.pixels.iter().map(|&d| char::from(b'0' + d)).collect()
+    }
+    /// The image you see looking down through the layers. Pixels transparent all the way
+    /// down stay transparent.
+    pub fn composite(&self, decoder: Decoder) -> Composite {
+        let size = self.layer_size();
+        let pixels = match decoder {
+            Decoder::ForLoop => {
+                let mut image = vec![TRANSPARENT; size];
+                for l in self.layers() {
+                    for (p, &c) in l.iter().enumerate() {
+                        if image[p] == TRANSPARENT && c != TRANSPARENT {
+                            image[p] = c;
+                        }
+                    }
+                }
+                image
+            }
+            Decoder::Transpose => {
+                let layer_count = self.layer_count();
+                let mut pixels = self.pixels.clone();
+                let mut scratch = vec![TRANSPARENT; max(size, layer_count)];
+                transpose_inplace(&mut pixels, &mut scratch, size, layer_count);
+                pixels
+                    .chunks(layer_count)
+                    .map(|l| l.iter().cloned().find(|&x| x != TRANSPARENT).unwrap_or(TRANSPARENT))
+                    .collect()
+            }
+            Decoder::Steps => (0..size)
+                .map(|p| {
+                    self.pixels
+                        .iter()
+                        .skip(p)
+                        .step_by(size)
+                        .cloned()
+                        .find(|&x| x != TRANSPARENT)
+                        .unwrap_or(TRANSPARENT)
+                })
+                .collect(),
+        };
+        Composite {
+            width: self.width,
+            height: self.height,
+            pixels,
+        }
+    }
+}
+
+/// A flattened image.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Composite {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+impl Composite {
+    /// The rows, top first, with white as `█`, black as ` ` and transparent as `X`.
+    pub fn grid(&self) -> Vec<Vec<char>> {
+        self.pixels
+            .chunks(self.width)
+            .map(|r| r.iter().cloned().map(conv).collect())
+            .collect()
+    }
+    /// As `grid`, the right way up for `render_char_map`.
+    pub fn point_map(&self) -> HashMap<Point, char> {
+        let top = self.height as isize - 1;
+        self.pixels
+            .iter()
+            .enumerate()
+            .map(|(p, &d)| {
+                let (x, y) = (p % self.width, p / self.width);
+                (Point(x as isize, top - y as isize), conv(d))
+            })
+            .collect()
+    }
+    /// Each row of `grid` on a new line.
+    pub fn render(&self) -> String {
+        self.grid()
+            .iter()
+            .map(|x| format!("\n{}", x.iter().format("")))
+            .join("")
+    }
+    pub fn read(&self) -> Reading {
+        read_grid(&self.grid())
+    }
+}
+
+pub fn parse(input: &str) -> SifImage {
+    SifImage::parse(input, WIDTH, HEIGHT).unwrap_or_else(|e| panic!("Bad image: {}", e))
 }
 
 #[aoc(day8, part1)]
 pub fn p1(input: &str) -> usize {
-    let fewest0 = input
-        .trim()
-        .chars()
-        .chunks(IM_SIZE)
-        .into_iter()
-        .map(std::iter::Iterator::collect::<Counter<_>>)
-        .min_by_key(|x| x[&'0'])
-        .unwrap();
-    fewest0[&'1'] * fewest0[&'2']
+    parse(input).checksum()
+}
+/// The message, or the image if it can't be read.
+#[aoc(day8, part2)]
+pub fn p2(input: &str) -> String {
+    let image = parse(input).composite(Decoder::Steps);
+    image.read().into_result().unwrap_or_else(|_| image.render())
 }
 #[aoc(day8, part2, forloop)]
 pub fn p2_forloop(input: &str) -> String {
-    let layers = input.trim().chars().map(conv).chunks(IM_SIZE);
-    let mut image = vec![vec!['X'; WIDTH]; HEIGHT];
-    for l in &layers {
-        for (p, c) in l.enumerate() {
-            let x = p % WIDTH;
-            let y = p / WIDTH;
-            if image[y][x] == 'X' && c != 'X' {
-                image[y][x] = c;
-            }
-        }
-    }
-    image
-        .iter()
-        .map(|x| format!("\n{}", x.iter().format("")))
-        .join("")
+    parse(input).composite(Decoder::ForLoop).render()
 }
 #[aoc(day8, part2, transpose)]
 pub fn p2_transpose(input: &str) -> String {
-    let mut pixels: Vec<_> = input.trim().chars().map(conv).collect();
-    let layer_count = pixels.len() / IM_SIZE;
-    let mut scratch = vec!['X'; max(IM_SIZE, layer_count)];
-    transpose_inplace(&mut pixels, &mut scratch, IM_SIZE, layer_count);
-    pixels
-        .chunks(layer_count)
-        .map(|l| l.iter().cloned().find(|&x| x != 'X').unwrap())
-        .chunks(WIDTH)
-        .into_iter()
-        .map(|x| format!("\n{}", x.format("")))
-        .join("")
+    parse(input).composite(Decoder::Transpose).render()
 }
 #[aoc(day8, part2, steps)]
 pub fn p2_steps(input: &str) -> String {
-    let pixels: Vec<_> = input.trim().chars().map(conv).collect();
-    (0..IM_SIZE)
-        .map(|p| {
-            pixels
-                .iter()
-                .skip(p)
-                .step_by(IM_SIZE)
-                .find(|&x| *x != 'X')
-                .unwrap()
+    parse(input).composite(Decoder::Steps).render()
+}
+
+#[test]
+pub fn sif_tests() {
+    let im = SifImage::parse("123456789012", 3, 2).unwrap();
+    assert_eq!(im.layer_count(), 2);
+    assert_eq!(im.layer(1), Some(&[7, 8, 9, 0, 1, 2][..]));
+    assert_eq!(im.layer(2), None);
+    assert_eq!(im.layer_with_fewest(0), 0);
+    assert_eq!(im.checksum(), 1);
+    assert_eq!(im.digit_counts(1).unwrap()[&9], 1);
+    assert_eq!(im.count(0, 2), Some(1));
+    assert_eq!(im.count(2, 2), None);
+    assert_eq!(im.encode(), "123456789012");
+    assert_eq!(
+        SifImage::parse("1234x", 2, 2),
+        Err(SifError::BadPixel { index: 4, c: 'x' })
+    );
+    assert_eq!(
+        SifImage::parse(" \n12 34\n", 2, 2),
+        Err(SifError::BadPixel { index: 4, c: ' ' })
+    );
+    assert_eq!(
+        SifImage::parse("12345", 2, 2),
+        Err(SifError::BadSize { len: 5, width: 2, height: 2 })
+    );
+
+    let im = SifImage::parse("0222112222120000", 2, 2).unwrap();
+    let c = im.composite(Decoder::ForLoop);
+    assert_eq!(c.pixels, vec![0, 1, 1, 0]);
+    assert_eq!(c.grid(), vec![vec![' ', '█'], vec!['█', ' ']]);
+    assert_eq!(c.point_map()[&Point(1, 1)], '█');
+    assert_eq!(c.render(), "\n █\n█ ");
+    let layers: Vec<Vec<u8>> = im.layers().map(<[u8]>::to_vec).collect();
+    assert_eq!(SifImage::from_layers(&layers, 2, 2), Ok(im));
+}
+
+#[test]
+pub fn decoders_agree() {
+    //mostly transparent layers, set at different depths, with the last column never set.
+    let layers: Vec<Vec<u8>> = (0..7)
+        .map(|l| {
+            (0..15)
+                .map(|p| {
+                    if p % 5 != 4 && (p + l) % 4 == 0 {
+                        ((p / 2 + l) % 2) as u8
+                    } else {
+                        TRANSPARENT
+                    }
+                })
+                .collect()
         })
-        .chunks(WIDTH)
-        .into_iter()
-        .map(|x| format!("\n{}", x.format("")))
-        .join("")
+        .collect();
+    let im = SifImage::from_layers(&layers, 5, 3).unwrap();
+    let expected = im.composite(Decoder::ForLoop);
+    assert!(expected.pixels.contains(&TRANSPARENT));
+    assert!(expected.pixels.contains(&WHITE));
+    for &d in &[Decoder::Transpose, Decoder::Steps] {
+        assert_eq!(im.composite(d), expected, "{:?}", d);
+    }
 }
@@ -1,9 +1,12 @@
-use crate::comp::Computer;
+use crate::comp::{run_diagnostics, Computer};
 use std::str::FromStr;
 #[aoc(day9, part1)]
 pub fn p1(input: &str) -> i64 {
-    let mut c = Computer::from_str(input).unwrap();
-    c.with_input(1).run().get_last_output()
+    let c = Computer::from_str(input).unwrap();
+    let report = run_diagnostics(&c, 1);
+    report
+        .passed_answer()
+        .unwrap_or_else(|| panic!("BOOST diagnostics failed:\n{}", report))
 }
 #[aoc(day9, part2)]
 pub fn p2(input: &str) -> i64 {
@@ -1,22 +1,14 @@
+use crate::utils::grid::Grid;
 use crate::utils::points::{Point, PolarCoord};
 use std::cmp::Reverse;
 use std::collections::{BinaryHeap, HashMap, HashSet};
 use std::f64::consts::FRAC_PI_2;
-use std::convert::TryInto;
 
 type AsteroidSet = HashSet<Point>;
 #[aoc_generator(day10)]
 pub fn gen(input: &str) -> AsteroidSet {
-    input
-        .lines()
-        .enumerate()
-        .flat_map(move |(y, l)| {
-            l.chars().enumerate().filter_map(move |(x, c)| match c {
-                '#' => Some(Point(x.try_into().unwrap(), y.try_into().unwrap())),
-                _ => None,
-            })
-        })
-        .collect()
+    let grid = Grid::parse(input);
+    grid.cells().filter(|c| *c.1 == '#').map(|c| c.0).collect()
 }
 
 #[aoc(day10, part1)]
@@ -1,3 +1,4 @@
+pub mod amplifiers;
 pub mod day06;
 pub mod day07;
 pub mod day08;
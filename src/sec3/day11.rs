@@ -1,86 +1,277 @@
-use crate::comp::Computer;
+use crate::comp::{Computer, DeviceError, Protocol};
+use crate::utils::ocr::read_point_map;
 use crate::utils::points::*;
+use itertools::Itertools;
 use std::collections::HashMap;
 use std::str::FromStr;
-use std::sync::mpsc;
-use std::thread;
 
 const WHITE: char = '█';
 const BLACK: char = '░';
 
-pub fn robot(
-    input: &mpsc::Receiver<isize>,
-    output: &mpsc::Sender<isize>,
-    c: char,
-) -> HashMap<Point, char> {
-    let mut painted_panels = HashMap::new();
-    let mut p = Point(0, 0);
-    let mut d = Dir::U;
-    painted_panels.insert(p, c);
-    loop {
-        if output
-            .send((painted_panels.get(&p) == Some(&WHITE)).into())
-            .is_err()
-        {
-            break;
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Turn {
+    Left,
+    Right,
+}
+
+/// Which brain outputs mean which way to turn.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TurnEncoding {
+    left: isize,
+    right: isize,
+}
+
+impl TurnEncoding {
+    /// `None` if `left` and `right` are the same, as then there's no telling them apart.
+    pub fn new(left: isize, right: isize) -> Option<Self> {
+        if left == right {
+            None
+        } else {
+            Some(Self { left, right })
         }
-        match input.recv() {
-            Ok(0) => painted_panels.insert(p, BLACK),
-            Ok(1) => painted_panels.insert(p, WHITE),
-            Err(_) => break,
-            _ => panic!("Unknown paint instruction"),
+    }
+    pub fn left(&self) -> isize {
+        self.left
+    }
+    pub fn right(&self) -> isize {
+        self.right
+    }
+}
+
+impl Default for TurnEncoding {
+    fn default() -> Self {
+        Self { left: 0, right: 1 }
+    }
+}
+
+/// The painting robot's brain takes the colour under the robot (`true` for white) and
+/// answers with the colour to paint (`true` for white) and which way to turn.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct HullProtocol {
+    pub turns: TurnEncoding,
+}
+
+impl Protocol<isize> for HullProtocol {
+    type Command = bool;
+    type Response = (bool, Turn);
+    const RESPONSE_LEN: usize = 2;
+    fn encode(&self, white: &bool) -> Vec<isize> {
+        vec![(*white).into()]
+    }
+    fn decode(&self, o: &[isize]) -> Result<(bool, Turn), DeviceError<isize>> {
+        let paint = match o[0] {
+            0 => false,
+            1 => true,
+            x => return Err(DeviceError::UnknownCode(x)),
         };
-        match input.recv() {
-            Ok(0) => {
-                d = d.rotate_left();
-            }
-            Ok(1) => d = d.rotate_left().rotate_left().rotate_left(),
-            Err(_) => break,
-            _ => panic!("Unknown turn instruction"),
+        let turn = match o[1] {
+            x if x == self.turns.left => Turn::Left,
+            x if x == self.turns.right => Turn::Right,
+            x => return Err(DeviceError::UnknownCode(x)),
+        };
+        Ok((paint, turn))
+    }
+}
+
+/// A panel being painted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PaintEvent {
+    pub step: usize,
+    pub position: Point,
+    pub white: bool,
+}
+
+/// The hull painting robot, run one step at a time by its brain.
+#[derive(Clone, Debug)]
+pub struct PaintingRobot {
+    pub position: Point,
+    pub facing: Dir,
+    start_white: bool,
+    panels: HashMap<Point, bool>,
+    history: Vec<PaintEvent>,
+    protocol: HullProtocol,
+}
+
+impl PaintingRobot {
+    /// At the origin facing up, on a panel of the given colour, on an otherwise black hull.
+    pub fn new(start_white: bool) -> Self {
+        Self {
+            position: Point(0, 0),
+            facing: Dir::U,
+            start_white,
+            panels: [(Point(0, 0), start_white)].iter().cloned().collect(),
+            history: Vec::new(),
+            protocol: HullProtocol::default(),
         }
-        p += d.as_point_delta();
     }
-    painted_panels
+    pub fn with_turns(mut self, turns: TurnEncoding) -> Self {
+        self.protocol.turns = turns;
+        self
+    }
+    pub fn is_white(&self, p: Point) -> bool {
+        self.panels.get(&p) == Some(&true)
+    }
+    /// Shows the brain the panel underneath, then paints, turns and moves as it says.
+    /// Returns false once the brain has halted, or an error if it halts part way through
+    /// an answer.
+    pub fn step(&mut self, brain: &mut Computer<isize>) -> Result<bool, DeviceError<isize>> {
+        let (white, turn) = match self.protocol.call(brain, &self.is_white(self.position)) {
+            Ok(r) => r,
+            Err(DeviceError::Halted) => return Ok(false),
+            Err(e) => return Err(e),
+        };
+        self.panels.insert(self.position, white);
+        self.history.push(PaintEvent {
+            step: self.history.len(),
+            position: self.position,
+            white,
+        });
+        self.facing = match turn {
+            Turn::Left => self.facing.rotate_left(),
+            Turn::Right => self.facing.rotate_right(),
+        };
+        self.position = self.position.step(self.facing);
+        Ok(true)
+    }
+    /// Steps until the brain halts.
+    pub fn run(&mut self, brain: &mut Computer<isize>) -> Result<(), DeviceError<isize>> {
+        while self.step(brain)? {}
+        Ok(())
+    }
+    pub fn history(&self) -> &[PaintEvent] {
+        &self.history
+    }
+    /// How many times each panel has been painted.
+    pub fn paint_counts(&self) -> HashMap<Point, usize> {
+        let mut ans = HashMap::new();
+        for e in &self.history {
+            *ans.entry(e.position).or_insert(0) += 1;
+        }
+        ans
+    }
+    pub fn panels_painted(&self) -> usize {
+        self.paint_counts().len()
+    }
+    pub fn white_panels(&self) -> usize {
+        self.panels.values().filter(|&&w| w).count()
+    }
+    /// The hull after the first `steps` steps. Panels the robot hasn't reached yet (but will)
+    /// are blank, so all frames are the same size.
+    pub fn frame(&self, steps: usize) -> HashMap<Point, char> {
+        let mut m: HashMap<Point, char> = self.history.iter().map(|e| (e.position, ' ')).collect();
+        let mut paint = |p, white| m.insert(p, if white { WHITE } else { BLACK });
+        paint(Point(0, 0), self.start_white);
+        for e in self.history.iter().take(steps) {
+            paint(e.position, e.white);
+        }
+        m
+    }
+    /// Renders the hull every `every` steps, and at the end.
+    pub fn time_lapse(&self, every: usize) -> Vec<String> {
+        let last = self.history.len();
+        (0..last)
+            .step_by(every.max(1))
+            .chain(std::iter::once(last))
+            .dedup()
+            .map(|s| render_char_map(&self.frame(s)))
+            .collect()
+    }
+    pub fn as_char_map(&self) -> HashMap<Point, char> {
+        self.panels
+            .iter()
+            .map(|(&p, &w)| (p, if w { WHITE } else { BLACK }))
+            .collect()
+    }
 }
-pub fn run(input: &str, init_c: char) -> HashMap<Point, char> {
-    let mut c: Computer<isize> = Computer::from_str(input).unwrap();
-    let (tx,rx) = c.make_io_chans();
-    let c_thr = thread::spawn(move || {
-        c.run();
-    });
-    let robot_thr = thread::spawn(move || robot(&rx, &tx, init_c));
-    c_thr.join().unwrap();
-    robot_thr.join().unwrap()
+
+pub fn paint(input: &str, start_white: bool) -> PaintingRobot {
+    let mut brain: Computer<isize> = Computer::from_str(input).unwrap();
+    let mut robot = PaintingRobot::new(start_white);
+    robot
+        .run(&mut brain)
+        .unwrap_or_else(|e| panic!("Robot error: {}", e));
+    robot
 }
 
 #[aoc(day11, part1)]
 pub fn p1(input: &str) -> usize {
-    run(input, BLACK).len()
+    paint(input, false).panels_painted()
 }
 
+/// The registration identifier, or the painting if it can't be read.
 #[aoc(day11, part2)]
 pub fn p2(input: &str) -> String {
-    crate::utils::points::render_char_map(&run(input, WHITE))
+    let hull = paint(input, true).as_char_map();
+    read_point_map(&hull)
+        .into_result()
+        .unwrap_or_else(|_| render_char_map(&hull))
 }
 #[test]
 pub fn example() {
-    let (txa, rxa) = mpsc::channel::<isize>();
-    let (txb, rxb) = mpsc::channel::<isize>();
-    let r = thread::spawn(move || robot(&rxb, &txa, BLACK));
-
-    let input: Vec<isize> = vec![1, 0, 0, 0, 1, 0, 1, 0, 0, 1, 1, 0, 1, 0];
-    let correct_output = [0, 0, 0, 0, 1, 0, 0, 0];
-    for i in input.iter() {
-        txb.send(*i).unwrap()
-    }
-    drop(txb);
-    let mut output = vec![];
-    loop {
-        match rxa.recv() {
-            Ok(i) => output.push(i),
-            Err(_) => break,
-        }
+    //the example's instructions, with the brain checking it's shown the panels it expects.
+    let brain = crate::comp::compile::<isize>(
+        "fn step(seen, paint, turn) { if (input() != seen) { output(9); } else { output(paint); } output(turn); }
+         fn main() {
+             step(0, 1, 0); step(0, 0, 0); step(0, 1, 0); step(0, 1, 0); step(1, 0, 1); step(0, 1, 0); step(0, 1, 0);
+             if (input() != 0) { output(9); }
+         }",
+    )
+    .unwrap();
+    let mut brain = Computer::new(&brain);
+    let mut robot = PaintingRobot::new(false);
+    let mut steps = 0;
+    while robot.step(&mut brain).unwrap() {
+        steps += 1;
     }
-    assert_eq!(output, correct_output);
-    assert_eq!(r.join().unwrap().len(), 6);
+    assert_eq!(steps, 7);
+    assert_eq!(robot.panels_painted(), 6);
+    assert_eq!(robot.white_panels(), 4);
+}
+
+#[test]
+pub fn hull_protocol() {
+    let hp = HullProtocol::default();
+    assert_eq!(hp.encode(&true), vec![1]);
+    assert_eq!(hp.decode(&[1, 0]), Ok((true, Turn::Left)));
+    assert_eq!(hp.decode(&[0, 1]), Ok((false, Turn::Right)));
+    assert_eq!(hp.decode(&[0, 2]), Err(DeviceError::UnknownCode(2)));
+    let swapped = HullProtocol {
+        turns: TurnEncoding::new(1, 0).unwrap(),
+    };
+    assert_eq!(swapped.decode(&[1, 0]), Ok((true, Turn::Right)));
+    assert_eq!(TurnEncoding::new(1, 1), None);
+}
+
+#[test]
+pub fn painting_robot() {
+    //the example's instructions, whatever the robot sees.
+    let brain = crate::comp::compile::<isize>(
+        "fn step(paint, turn) { input(); output(paint); output(turn); }
+         fn main() { step(1, 0); step(0, 0); step(1, 0); step(1, 0); step(0, 1); step(1, 0); step(1, 0); }",
+    )
+    .unwrap();
+    let mut robot = PaintingRobot::new(false);
+    robot.run(&mut Computer::new(&brain)).unwrap();
+    assert_eq!(robot.history().len(), 7);
+    assert_eq!(robot.panels_painted(), 6);
+    assert_eq!(robot.white_panels(), 4);
+    assert_eq!(robot.paint_counts()[&Point(0, 0)], 2);
+    assert_eq!((robot.position, robot.facing), (Point(0, 1), Dir::L));
+    let frames = robot.time_lapse(3);
+    assert_eq!(frames.len(), 4);
+    assert_eq!(frames[0], "\n   \n ░ \n   ");
+    assert_eq!(frames[3], render_char_map(&robot.as_char_map()));
+    //with the turns the other way round, it spirals the other way.
+    let mut robot = PaintingRobot::new(false).with_turns(TurnEncoding::new(1, 0).unwrap());
+    robot.run(&mut Computer::new(&brain)).unwrap();
+    assert_eq!((robot.position, robot.facing), (Point(0, 1), Dir::R));
+    //halting after painting, but before saying which way to turn, is an error.
+    let brain = crate::comp::compile::<isize>("fn main() { input(); output(1); }").unwrap();
+    let mut robot = PaintingRobot::new(false);
+    let err = DeviceError::WrongOutputCount {
+        expected: 2,
+        got: vec![1],
+    };
+    assert_eq!(robot.step(&mut Computer::new(&brain)), Err(err));
+    assert!(robot.history().is_empty());
 }
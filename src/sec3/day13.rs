@@ -1,54 +1,203 @@
-use crate::comp::Computer;
-use crate::utils::points::Point;
+use crate::comp::{Computer, DeviceError, Patch};
+use crate::utils::points::{render_char_map, Point};
+use log::info;
+use num_enum::TryFromPrimitive;
 use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::time::Duration;
+
+#[derive(Clone, Copy, TryFromPrimitive, PartialEq, Eq, Hash, Debug)]
+#[repr(isize)]
+pub enum Tile {
+    Empty = 0,
+    Wall = 1,
+    Block = 2,
+    Paddle = 3,
+    Ball = 4,
+}
+
+impl Tile {
+    pub fn symbol(self) -> char {
+        match self {
+            Self::Empty => ' ',
+            Self::Wall => '█',
+            Self::Block => 'x',
+            Self::Paddle => 'ￚ',
+            Self::Ball => '●',
+        }
+    }
+}
 
 pub fn symbol_for(i: isize) -> char {
-    match i {
-        0 => ' ',
-        1 => '█',
-        2 => 'x',
-        3 => 'ￚ',
-        4 => '●',
-        _ => panic!("Unknown block {}", i),
+    Tile::try_from(i).map_or('?', Tile::symbol)
+}
+
+/// The arcade cabinet's display, built up from the game's `(x, y, tile)` output triples.
+#[derive(Clone, Debug, Default)]
+pub struct ArcadeScreen {
+    pub tiles: HashMap<Point, Tile>,
+    pub score: isize,
+    pub ball: Option<Point>,
+    pub paddle: Option<Point>,
+}
+
+impl ArcadeScreen {
+    pub fn new() -> Self {
+        Self::default()
     }
+    /// Applies a batch of output triples. `(-1, 0, s)` sets the score; unknown tiles are
+    /// logged and skipped. Overwriting the ball or paddle forgets where it was. Whole triples
+    /// are applied even if there's a part of one left over at the end, which is an error.
+    pub fn apply(&mut self, output: &[isize]) -> Result<&mut Self, DeviceError<isize>> {
+        let triples = output.chunks_exact(3);
+        let rest = triples.remainder();
+        for v in triples {
+            if v[0] == -1 && v[1] == 0 {
+                self.score = v[2];
+                continue;
+            }
+            let p = Point(v[0], v[1]);
+            match Tile::try_from(v[2]) {
+                Ok(t) => {
+                    if self.ball == Some(p) && t != Tile::Ball {
+                        self.ball = None;
+                    }
+                    if self.paddle == Some(p) && t != Tile::Paddle {
+                        self.paddle = None;
+                    }
+                    match t {
+                        Tile::Ball => self.ball = Some(p),
+                        Tile::Paddle => self.paddle = Some(p),
+                        _ => (),
+                    }
+                    self.tiles.insert(p, t);
+                }
+                Err(_) => info!("Unknown tile {} at {:?}", v[2], p),
+            }
+        }
+        if rest.is_empty() {
+            Ok(self)
+        } else {
+            Err(DeviceError::WrongOutputCount {
+                expected: 3,
+                got: rest.to_vec(),
+            })
+        }
+    }
+    pub fn count(&self, t: Tile) -> usize {
+        self.tiles.values().filter(|&&x| x == t).count()
+    }
+    pub fn tile_counts(&self) -> HashMap<Tile, usize> {
+        let mut counts = HashMap::new();
+        for &t in self.tiles.values() {
+            *counts.entry(t).or_insert(0) += 1;
+        }
+        counts
+    }
+    /// The score line followed by the screen. Screen y goes down the page, so it's flipped
+    /// for `render_char_map`.
+    pub fn render(&self) -> String {
+        let m: HashMap<Point, char> = self
+            .tiles
+            .iter()
+            .map(|(p, t)| (Point(p.0, -p.1), t.symbol()))
+            .collect();
+        let grid = if m.is_empty() {
+            String::new()
+        } else {
+            render_char_map(&m)
+        };
+        format!("Score: {}{}", self.score, grid)
+    }
+}
+
+pub fn insert_coins() -> Patch<isize> {
+    Patch::new("insert_coins", "Play the arcade game for free").with(0, 2)
 }
+
+/// An autoplayer that moves the paddle towards the ball.
+pub fn follow_ball(s: &ArcadeScreen) -> isize {
+    match (s.ball, s.paddle) {
+        (Some(b), Some(p)) => (b.0 - p.0).signum(),
+        _ => 0,
+    }
+}
+
+/// Plays the game until it halts, asking `joystick` for each move and calling `on_frame`
+/// each time the game waits for input. Returns the final screen, or an error if the game
+/// stops part way through a triple.
+pub fn play<J, F>(
+    c: &mut Computer<isize>,
+    mut joystick: J,
+    mut on_frame: F,
+) -> Result<ArcadeScreen, DeviceError<isize>>
+where
+    J: FnMut(&ArcadeScreen) -> isize,
+    F: FnMut(&ArcadeScreen),
+{
+    let mut screen = ArcadeScreen::new();
+    while !c.is_halted() {
+        c.run_to_input();
+        screen.apply(&c.take_output())?;
+        on_frame(&screen);
+        c.with_input(joystick(&screen));
+    }
+    Ok(screen)
+}
+
+/// Plays the game with coins in, printing every frame to the terminal.
+pub fn replay(input: &str, frame_delay: Duration) -> isize {
+    let mut c = input.parse::<Computer<isize>>().unwrap();
+    insert_coins().apply(&mut c);
+    play(&mut c, follow_ball, |s| {
+        print!("\x1b[2J\x1b[H{}", s.render());
+        let _ = std::io::Write::flush(&mut std::io::stdout());
+        std::thread::sleep(frame_delay);
+    })
+    .unwrap_or_else(|e| panic!("Game error: {}", e))
+    .score
+}
+
 #[aoc(day13, part1)]
 pub fn p1(input: &str) -> usize {
     let mut c = input.parse::<Computer<isize>>().unwrap();
-    let mut screen = HashMap::new();
     c.run();
-    for v in c.get_output().chunks(3) {
-        let e = screen.entry(Point(v[0], v[1])).or_default();
-        *e = v[2];
-    }
-    screen.values().filter(|&x| *x == 2).count()
+    ArcadeScreen::new()
+        .apply(c.get_output())
+        .unwrap_or_else(|e| panic!("Game error: {}", e))
+        .count(Tile::Block)
 }
 #[aoc(day13, part2)]
 pub fn p2(input: &str) -> isize {
     let mut c = input.parse::<Computer<isize>>().unwrap();
-    c.abs_store(0, 2);
-    //we don't actually need to keep track of the screen, but it feels weird not to.
-    let mut screen = HashMap::new();
-    let mut paddle_x = 0;
-    let mut ball_x = 0;
-    let mut score = 0;
-    while !c.is_halted() {
-        c.run_to_input();
-        for v in c.take_output().chunks(3) {
-            if v[0] == -1 && v[1] == 0 {
-                score = v[2];
-            } else {
-                let sym = symbol_for(v[2]);
-                match sym {
-                    '●' => ball_x = v[0],
-                    'ￚ' => paddle_x = v[0],
-                    _ => (),
-                }
-                *screen.entry(Point(v[0], v[1])).or_default() = sym;
-            }
-        }
-        let correct_move = (ball_x - paddle_x).signum();
-        c.with_input(correct_move);
-    }
-    score
+    insert_coins().apply(&mut c);
+    play(&mut c, follow_ball, |_| ())
+        .unwrap_or_else(|e| panic!("Game error: {}", e))
+        .score
+}
+
+#[test]
+pub fn screen_tests() {
+    let mut s = ArcadeScreen::new();
+    s.apply(&[0, 0, 1, 1, 0, 1, 2, 0, 1, 0, 1, 2, 1, 1, 4, 1, 2, 3, -1, 0, 12, 2, 2, 7]).unwrap();
+    assert_eq!(s.score, 12);
+    assert_eq!(s.ball, Some(Point(1, 1)));
+    assert_eq!(s.paddle, Some(Point(1, 2)));
+    assert_eq!(s.count(Tile::Wall), 3);
+    assert_eq!(s.tile_counts()[&Tile::Block], 1);
+    assert_eq!(s.render(), "Score: 12\n███\nx● \n ￚ ");
+    s.apply(&[1, 1, 0, 2, 1, 4]).unwrap();
+    assert_eq!(s.ball, Some(Point(2, 1)));
+    assert_eq!(follow_ball(&s), 1);
+    s.apply(&[2, 1, 0, 1, 2, 2]).unwrap();
+    assert_eq!((s.ball, s.paddle), (None, None));
+    assert_eq!(follow_ball(&s), 0);
+    //a game stopped part way through a triple.
+    let err = DeviceError::WrongOutputCount {
+        expected: 3,
+        got: vec![0, 0],
+    };
+    assert_eq!(s.apply(&[0, 0, 4, 0, 0]).map(|s| s.ball), Err(err));
+    assert_eq!(s.ball, Some(Point(0, 0)));
+    assert_eq!(symbol_for(7), '?');
 }
@@ -1,119 +1,401 @@
-use crate::comp::Computer;
-use crate::utils::points::{Dir, Point};
+use crate::comp::{BridgeMode, Computer, DeviceError, Protocol, Server};
+use crate::utils::grid::Grid;
+use crate::utils::points::{Aabb, Dir, Point};
+use crate::utils::algorithms::bfs_dist_all;
+use crate::utils::points::render_char_map_w;
 use log::info;
-use std::collections::hash_map::Entry;
-use std::collections::HashMap;
-use std::convert::TryInto;
-use std::hash::BuildHasher;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::str::FromStr;
 
 const WALL: char = '█';
-const SPACE: char = '.';
-const EXPLORED_SPACE: char = ' ';
+const SPACE: char = ' ';
 const UNKNOWN: char = '░';
 const OXYGEN: char = 'O';
-const DEAD_END: char = 'D';
-const BRANCH: char = '╳';
 const START: char = 'S';
+const SPREADING: char = 'o';
+const BLOCKED: char = '▓';
 
-const RESPS: [char; 3] = [WALL, SPACE, OXYGEN];
-pub fn try_move(c: &mut Computer<i32>, d: Dir) -> char {
-    let i = match d {
-        Dir::U => 1,
-        Dir::D => 2,
-        Dir::L => 3,
-        Dir::R => 4,
-    };
-    c.with_input(i).run_to_input();
-    let o = c.take_output();
-    assert_eq!(o.len(), 1);
-    let o_u: usize = o[0].try_into().unwrap();
-    RESPS[o_u]
-}
-
-/// Performs a breadth first search of the map, and returns a map of point to distance from the start.
-pub fn bfs_depth<S: BuildHasher>(map: &HashMap<Point, char, S>, start: Point) -> HashMap<Point, u32> {
-    let mut points = std::collections::VecDeque::new();
-    points.push_back((start, 0));
-    let mut min_dist_map = HashMap::new();
-    min_dist_map.insert(start, 0);
-    while !points.is_empty() {
-        let (pos, count) = points.pop_front().unwrap();
-        Dir::all().iter().for_each(|d| {
-            let p2 = pos + d.as_point_delta();
-            if map.get(&p2) != Some(&WALL) && !min_dist_map.contains_key(&p2) {
-                min_dist_map.insert(p2, count + 1);
-                let t = (p2, count + 1);
-                points.push_back(t);
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Status {
+    Wall,
+    Moved,
+    Oxygen,
+}
+
+/// The repair droid takes a direction and says what it found there.
+pub struct DroidProtocol;
+
+impl Protocol<i32> for DroidProtocol {
+    type Command = Dir;
+    type Response = Status;
+    const RESPONSE_LEN: usize = 1;
+    fn encode(&self, d: &Dir) -> Vec<i32> {
+        vec![match d {
+            Dir::U => 1,
+            Dir::D => 2,
+            Dir::L => 3,
+            Dir::R => 4,
+        }]
+    }
+    fn decode(&self, o: &[i32]) -> Result<Status, DeviceError<i32>> {
+        match o[0] {
+            0 => Ok(Status::Wall),
+            1 => Ok(Status::Moved),
+            2 => Ok(Status::Oxygen),
+            x => Err(DeviceError::UnknownCode(x)),
+        }
+    }
+}
+
+/// Hosts the droid on `addr` (a loopback address), to drive from another process: send a
+/// direction (1-4), get back a status (0 wall, 1 moved, 2 found the oxygen system).
+pub fn serve(input: &str, addr: &str) -> std::io::Result<()> {
+    let c: Computer<i32> = input.parse().unwrap();
+    Server::bind(addr, c, BridgeMode::Numeric)?.serve()
+}
+
+pub fn try_move(c: &mut Computer<i32>, d: Dir) -> Status {
+    DroidProtocol
+        .call(c, &d)
+        .unwrap_or_else(|e| panic!("Droid error: {}", e))
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseMapError {
+    /// A character which isn't part of the map, at a (1-based) line and column.
+    BadChar { line: usize, col: usize, c: char },
+    NoStart,
+    TwoStarts,
+}
+
+impl std::fmt::Display for ParseMapError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::BadChar { line, col, c } => write!(f, "unexpected '{}' at {}:{}", c, line, col),
+            Self::NoStart => write!(f, "map has no start"),
+            Self::TwoStarts => write!(f, "map has more than one start"),
+        }
+    }
+}
+
+/// What the droid found exploring the ship.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ShipMap {
+    pub walls: HashSet<Point>,
+    /// Every cell the droid can move to, including the start and the oxygen system.
+    pub open: HashSet<Point>,
+    pub oxygen: Option<Point>,
+    pub start: Point,
+    /// For each open cell but the start, where the droid first reached it from, and how.
+    parents: HashMap<Point, (Point, Dir)>,
+}
+
+impl ShipMap {
+    fn new(start: Point) -> Self {
+        Self {
+            walls: HashSet::new(),
+            open: [start].iter().cloned().collect(),
+            oxygen: None,
+            start,
+            parents: HashMap::new(),
+        }
+    }
+    pub fn is_known(&self, p: Point) -> bool {
+        self.open.contains(&p) || self.walls.contains(&p)
+    }
+    /// The moves which take the droid from the start to `p`, if it's been there.
+    pub fn path_to(&self, mut p: Point) -> Option<Vec<Dir>> {
+        if !self.open.contains(&p) {
+            return None;
+        }
+        let mut path = Vec::new();
+        while let Some(&(from, d)) = self.parents.get(&p) {
+            path.push(d);
+            p = from;
+        }
+        path.reverse();
+        Some(path)
+    }
+    /// The open cells, as a grid just big enough to hold them.
+    pub fn open_grid(&self) -> Grid<bool> {
+        let bb = self.open.iter().fold(Aabb::new(self.start), |bb, &p| bb.extend(p));
+        let mut g = Grid::new(bb.width(), bb.height(), false).with_origin(bb.bottom_left);
+        for &p in &self.open {
+            g[p] = true;
+        }
+        g
+    }
+    /// The distance from `from` to every open cell reachable from it.
+    pub fn distances_from(&self, from: Point) -> HashMap<Point, u32> {
+        let open = self.open_grid();
+        bfs_dist_all(&from, |&p| {
+            open.neighbours(p)
+                .filter(|&n| open[n])
+                .map(|n| (n, 1))
+                .collect::<Vec<_>>()
+        })
+    }
+    pub fn as_char_map(&self) -> HashMap<Point, char> {
+        let mut m: HashMap<Point, char> = self.walls.iter().map(|&p| (p, WALL)).collect();
+        m.extend(self.open.iter().map(|&p| (p, SPACE)));
+        if let Some(o) = self.oxygen {
+            m.insert(o, OXYGEN);
+        }
+        m.insert(self.start, START);
+        m
+    }
+    /// The map as text, north up, with cells nobody's seen as `░`. `parse` reads it back.
+    pub fn render(&self) -> String {
+        render_text(&self.as_char_map(), 1)
+    }
+    /// Reads a rendered map. The start is put at the origin, and the droid's paths are taken
+    /// to be the shortest ones.
+    pub fn parse(s: &str) -> Result<Self, ParseMapError> {
+        //a space is an open cell, so short lines are padded with unknown cells instead.
+        let grid = Grid::parse_padded(s, UNKNOWN);
+        let mut start = None;
+        for (p, &ch) in grid.cells() {
+            match ch {
+                START if start.is_some() => return Err(ParseMapError::TwoStarts),
+                START => start = Some(p),
+                WALL | SPACE | OXYGEN | UNKNOWN => (),
+                _ => {
+                    let (line, col) = (p.1 as usize + 1, p.0 as usize + 1);
+                    return Err(ParseMapError::BadChar { line, col, c: ch });
+                }
             }
-        });
+        }
+        let origin = start.ok_or(ParseMapError::NoStart)?;
+        let mut map = Self::new(Point(0, 0));
+        for (p, &ch) in grid.cells() {
+            //the text has north up, so flip it over.
+            let p = Point(p.0 - origin.0, origin.1 - p.1);
+            match ch {
+                WALL => {
+                    map.walls.insert(p);
+                }
+                UNKNOWN => (),
+                _ => {
+                    map.open.insert(p);
+                }
+            }
+            if ch == OXYGEN {
+                map.oxygen = Some(p);
+            }
+        }
+        let mut todo = std::collections::VecDeque::new();
+        todo.push_back(map.start);
+        while let Some(p) = todo.pop_front() {
+            for &d in &Dir::all() {
+                let n = p.step(d);
+                if n != map.start && map.open.contains(&n) && !map.parents.contains_key(&n) {
+                    map.parents.insert(n, (p, d));
+                    todo.push_back(n);
+                }
+            }
+        }
+        Ok(map)
     }
-    min_dist_map
 }
 
-/// Returns a map of the explored area. chars used:
-///  * '.': space, but not explored yet. Should not occur in output.
-///  * ' ': space, after exploration.
-///  * 'X': space, was a choice point.
-///  * 'D': space, was a dead end.
-///  * 'O': space, containing oxygen generator
-///  * 'S': space, starting position
-///  * '#': wall
+impl FromStr for ShipMap {
+    type Err = ParseMapError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+/// North (the highest y) first.
+fn render_text(m: &HashMap<Point, char>, width: u8) -> String {
+    let grid = Grid::from_map(m, UNKNOWN);
+    grid.rows()
+        .rev()
+        .map(|r| {
+            r.iter()
+                .flat_map(|&c| std::iter::repeat(c).take(width.into()))
+                .chain(Some('\n'))
+                .collect::<String>()
+        })
+        .collect()
+}
+
+/// Oxygen spreading out from some sources, one step each minute, through the open cells
+/// which aren't blocked. Iterating gives the cells newly filled each minute.
+#[derive(Clone, Debug)]
+pub struct OxygenSpread<'a> {
+    map: &'a ShipMap,
+    open: Grid<bool>,
+    blocked: HashSet<Point>,
+    filled: HashSet<Point>,
+    newest: HashSet<Point>,
+    minute: usize,
+}
+
+impl<'a> OxygenSpread<'a> {
+    /// Sources which aren't open cells are ignored.
+    pub fn new(map: &'a ShipMap, sources: &[Point]) -> Self {
+        let open = map.open_grid();
+        let filled: HashSet<Point> = sources.iter().filter(|&&p| open.get(p) == Some(&true)).cloned().collect();
+        Self {
+            map,
+            open,
+            blocked: HashSet::new(),
+            newest: filled.clone(),
+            filled,
+            minute: 0,
+        }
+    }
+    /// Stops oxygen getting into `cells`.
+    pub fn block<I: IntoIterator<Item = Point>>(mut self, cells: I) -> Self {
+        for p in cells {
+            self.filled.remove(&p);
+            self.newest.remove(&p);
+            self.blocked.insert(p);
+        }
+        self
+    }
+    pub fn minute(&self) -> usize {
+        self.minute
+    }
+    pub fn filled(&self) -> &HashSet<Point> {
+        &self.filled
+    }
+    /// How many minutes until the oxygen stops spreading.
+    pub fn fill_time(&self) -> usize {
+        self.minute + self.clone().count()
+    }
+    /// The open cells the oxygen will never reach.
+    pub fn unreachable(&self) -> HashSet<Point> {
+        let mut end = self.clone();
+        while end.next().is_some() {}
+        let done = |p: &&Point| end.filled.contains(p) || end.blocked.contains(p);
+        self.map.open.iter().filter(|p| !done(p)).cloned().collect()
+    }
+    /// The map with oxygen as `O`, what spread there in the last minute as `o`, and blocked
+    /// cells as `▓`, each `width` characters wide.
+    pub fn render(&self, width: u8) -> String {
+        let mut m = self.map.as_char_map();
+        m.extend(self.filled.iter().map(|&p| (p, OXYGEN)));
+        m.extend(self.newest.iter().map(|&p| (p, SPREADING)));
+        m.extend(self.blocked.iter().map(|&p| (p, BLOCKED)));
+        render_text(&m, width)
+    }
+}
+
+impl Iterator for OxygenSpread<'_> {
+    type Item = HashSet<Point>;
+    fn next(&mut self) -> Option<HashSet<Point>> {
+        let next: HashSet<Point> = self
+            .newest
+            .iter()
+            .flat_map(|&p| self.open.neighbours(p))
+            .filter(|p| {
+                self.open[*p] && !self.blocked.contains(p) && !self.filled.contains(p)
+            })
+            .collect();
+        if next.is_empty() {
+            return None;
+        }
+        self.minute += 1;
+        self.filled.extend(next.iter().cloned());
+        self.newest = next.clone();
+        Some(next)
+    }
+}
+
+/// Drives the droid into every reachable part of the ship.
 #[aoc_generator(day15)]
-pub fn explore(input: &str) -> HashMap<Point, char> {
+pub fn explore(input: &str) -> ShipMap {
     let c = input.parse::<Computer>().unwrap();
-    let position = Point(0, 0);
-    let mut known_map: HashMap<Point, char> = [(position, START)].iter().cloned().collect();
-    let mut save_points: Vec<(Point, Computer<i32>)> = vec![(position, c)];
-    while !save_points.is_empty() {
-        let (position, c) = save_points.pop().unwrap();
+    let mut map = ShipMap::new(Point(0, 0));
+    let mut save_points: Vec<(Point, Computer<i32>)> = vec![(map.start, c)];
+    while let Some((position, c)) = save_points.pop() {
         //scan around in directions we don't know.
-        let mut dirs: Vec<(Point, Computer)> = Dir::all()
-            .iter()
-            .filter_map(|&d| {
-                let new_pos = position.step(d);
-                match known_map.entry(new_pos) {
-                    Entry::Occupied(_) => None,
-                    Entry::Vacant(e) => {
-                        let mut c2 = c.clone();
-                        let ch = try_move(&mut c2, d);
-                        e.insert(ch);
-                        if ch == SPACE {
-                            Some((new_pos, c2))
-                        } else {
-                            None
-                        }
+        for &d in &Dir::all() {
+            let new_pos = position.step(d);
+            if map.is_known(new_pos) {
+                continue;
+            }
+            let mut c2 = c.clone();
+            match try_move(&mut c2, d) {
+                Status::Wall => {
+                    map.walls.insert(new_pos);
+                }
+                s => {
+                    if s == Status::Oxygen {
+                        map.oxygen = Some(new_pos);
                     }
+                    map.open.insert(new_pos);
+                    map.parents.insert(new_pos, (position, d));
+                    save_points.push((new_pos, c2));
                 }
-            })
-            .collect();
-        //mark points of interest.
-        match dirs.len() {
-            0 => known_map.insert(position, DEAD_END),
-            1 => known_map.insert(position, EXPLORED_SPACE),
-            _ => known_map.insert(position, BRANCH),
-        };
-        save_points.append(&mut dirs);
+            }
+        }
+    }
+    map
+}
+
+/// Reads the map from `path` if it's there, otherwise explores it and saves it there.
+pub fn explore_cached<P: AsRef<Path>>(input: &str, path: P) -> std::io::Result<ShipMap> {
+    if let Some(m) = std::fs::read_to_string(&path).ok().and_then(|s| s.parse().ok()) {
+        return Ok(m);
     }
-    known_map
+    let m = explore(input);
+    std::fs::write(path, m.render())?;
+    Ok(m)
 }
 
 #[aoc(day15, part1)]
-pub fn p1<S: BuildHasher>(input: &HashMap<Point, char, S>) -> u32 {
-    let (o_pos, _) = input
-        .iter()
-        .find(|(_, &v)| v == OXYGEN)
-        .expect("No oxygen!");
-    bfs_depth(input, Point(0, 0))[o_pos]
+pub fn p1(input: &ShipMap) -> u32 {
+    let o_pos = input.oxygen.expect("No oxygen!");
+    input.distances_from(input.start)[&o_pos]
 }
 #[aoc(day15, part2)]
-pub fn p2<S:BuildHasher>(input: &HashMap<Point, char, S>) -> u32 {
-    let (&o_pos, _) = input
-        .iter()
-        .find(|(_, &v)| v == OXYGEN)
-        .expect("No oxygen!");
-    info!(
-        "{}",
-        crate::utils::points::render_char_map_w(input, 2, UNKNOWN)
+pub fn p2(input: &ShipMap) -> u32 {
+    let o_pos = input.oxygen.expect("No oxygen!");
+    info!("{}", render_char_map_w(&input.as_char_map(), 2, UNKNOWN));
+    *input.distances_from(o_pos).values().max().unwrap()
+}
+
+#[test]
+pub fn ship_map_tests() {
+    let text = "░███░\n█ S █\n█ █O█\n░███░\n";
+    let m: ShipMap = text.parse().unwrap();
+    assert_eq!(m.start, Point(0, 0));
+    assert_eq!(m.oxygen, Some(Point(1, -1)));
+    assert_eq!(m.open.len(), 5);
+    assert_eq!(m.walls.len(), 11);
+    assert_eq!(m.path_to(Point(1, -1)), Some(vec![Dir::R, Dir::D]));
+    assert_eq!(m.path_to(Point(0, 0)), Some(vec![]));
+    assert_eq!(m.path_to(Point(0, -1)), None);
+    assert_eq!(p1(&m), 2);
+    assert_eq!(p2(&m), 4);
+    assert_eq!(m.render(), text);
+    assert_eq!(
+        "█S\n█x".parse::<ShipMap>(),
+        Err(ParseMapError::BadChar { line: 2, col: 2, c: 'x' })
     );
-    *bfs_depth(input, o_pos).values().max().unwrap()
+    assert_eq!("█ ".parse::<ShipMap>(), Err(ParseMapError::NoStart));
+    //the short line isn't padded out with open cells.
+    assert_eq!("███\n█S\n███\n".parse::<ShipMap>().unwrap().open.len(), 1);
+}
+
+#[test]
+pub fn oxygen_spread_tests() {
+    let m: ShipMap = "░███░\n█ S █\n█ █O█\n░███░\n".parse().unwrap();
+    let o = m.oxygen.unwrap();
+    let frames: Vec<_> = OxygenSpread::new(&m, &[o]).collect();
+    assert_eq!(frames.len(), 4);
+    assert_eq!(frames[0], [Point(1, 0)].iter().cloned().collect());
+    assert_eq!(OxygenSpread::new(&m, &[o]).fill_time(), p2(&m) as usize);
+    let two = OxygenSpread::new(&m, &[o, Point(-1, -1)]);
+    assert_eq!(two.fill_time(), 2);
+    assert!(two.unreachable().is_empty());
+    let mut blocked = OxygenSpread::new(&m, &[o]).block(vec![Point(0, 0)]);
+    assert_eq!(blocked.fill_time(), 1);
+    assert_eq!(blocked.unreachable(), [Point(-1, 0), Point(-1, -1)].iter().cloned().collect());
+    blocked.next();
+    assert_eq!(blocked.minute(), 1);
+    assert_eq!(blocked.render(1), "░███░\n█ ▓o█\n█ █O█\n░███░\n");
 }
@@ -1,15 +1,20 @@
-use crate::utils::points::as_point_map;
+use crate::utils::grid::Grid;
+use crate::comp::Patch;
 use crate::utils::prelude::*;
 
+const SCAFFOLD: char = '#';
+const MAX_ROUTINE_LEN: usize = 20;
+const ROUTINE_NAMES: [char; 3] = ['A', 'B', 'C'];
+
 #[aoc(day17, part1)]
 pub fn p1(input: &str) -> isize {
     let mut c: Computer = input.parse().unwrap();
     let output = c.run().output_as_string();
-    let g = as_point_map(&output);
-    g.iter()
+    let g = Grid::parse(&output);
+    g.cells()
         .filter_map(|(p, c)| {
-            if c == &'#' {
-                let nc = p.neighbours().iter().filter(|pn| g.get(pn) == Some(&'#')).count();
+            if c == &SCAFFOLD {
+                let nc = g.neighbours(p).filter(|&pn| g[pn] == SCAFFOLD).count();
                 if nc == 4 {
                     Some(p.0 * p.1)
                 } else { None }
@@ -18,18 +23,291 @@ pub fn p1(input: &str) -> isize {
         .sum()
 }
 
+/// One movement command: turn left or right, then go forward some distance.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Move {
+    pub turn: char,
+    pub dist: usize,
+}
+
+impl Move {
+    fn as_text(self) -> String {
+        format!("{},{}", self.turn, self.dist)
+    }
+}
+
+/// The robot's direction for a camera symbol. `Grid::parse` has y increasing down the
+/// screen, so the screen is mirrored relative to `Dir`: screen-up is `Dir::D`.
+fn robot_dir(c: char) -> Option<Dir> {
+    match c {
+        '^' => Some(Dir::D),
+        'v' => Some(Dir::U),
+        '<' => Some(Dir::L),
+        '>' => Some(Dir::R),
+        _ => None,
+    }
+}
+
+/// Follows the scaffold from the robot, going straight over intersections, and returns the
+/// turn/forward commands that cover it.
+pub fn trace_path(map: &Grid<char>) -> Vec<Move> {
+    let (mut pos, mut dir) = map
+        .cells()
+        .find_map(|(p, &c)| robot_dir(c).map(|d| (p, d)))
+        .expect("No robot on camera");
+    let is_scaffold = |p: Point| map.get(p) == Some(&SCAFFOLD);
+    let mut moves = Vec::new();
+    loop {
+        //mirrored screen, so Dir's right turn is the robot's left.
+        let (turn, new_dir) = if is_scaffold(pos.step(dir.rotate_right())) {
+            ('L', dir.rotate_right())
+        } else if is_scaffold(pos.step(dir.rotate_left())) {
+            ('R', dir.rotate_left())
+        } else {
+            break moves;
+        };
+        dir = new_dir;
+        let mut dist = 0;
+        while is_scaffold(pos.step(dir)) {
+            pos = pos.step(dir);
+            dist += 1;
+        }
+        moves.push(Move { turn, dist });
+    }
+}
+
+pub fn routine_text(moves: &[Move]) -> String {
+    moves.iter().map(|m| m.as_text()).join(",")
+}
+
+/// A path split into a main routine (indexes into `routines`) and up to three movement
+/// routines, each of which fits in the robot's memory.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Compressed {
+    pub main: Vec<usize>,
+    pub routines: Vec<Vec<Move>>,
+}
+
+impl Compressed {
+    /// The text to feed the vacuum robot, without the trailing video feed answer.
+    pub fn as_program(&self) -> String {
+        let mut ans = self.main.iter().map(|&i| ROUTINE_NAMES[i]).join(",");
+        ans.push('\n');
+        for ix in 0..ROUTINE_NAMES.len() {
+            ans.push_str(&self.routines.get(ix).map_or(String::new(), |r| routine_text(r)));
+            ans.push('\n');
+        }
+        ans
+    }
+}
+
+/// Searches for a decomposition of `path` into at most three routines which each fit in
+/// 20 characters, with a main routine that also fits.
+pub fn compress(path: &[Move]) -> Option<Compressed> {
+    let mut c = Compressed {
+        main: Vec::new(),
+        routines: Vec::new(),
+    };
+    if compress_from(path, &mut c) {
+        Some(c)
+    } else {
+        None
+    }
+}
+
+fn compress_from(rest: &[Move], c: &mut Compressed) -> bool {
+    if rest.is_empty() {
+        return true;
+    }
+    if 2 * c.main.len() + 1 > MAX_ROUTINE_LEN {
+        return false;
+    }
+    for ix in 0..c.routines.len() {
+        if rest.starts_with(&c.routines[ix]) {
+            c.main.push(ix);
+            if compress_from(&rest[c.routines[ix].len()..], c) {
+                return true;
+            }
+            c.main.pop();
+        }
+    }
+    if c.routines.len() < ROUTINE_NAMES.len() {
+        let new_ix = c.routines.len();
+        for len in (1..=rest.len()).rev() {
+            if routine_text(&rest[..len]).len() > MAX_ROUTINE_LEN {
+                continue;
+            }
+            c.routines.push(rest[..len].to_vec());
+            c.main.push(new_ix);
+            if compress_from(&rest[len..], c) {
+                return true;
+            }
+            c.main.pop();
+            c.routines.pop();
+        }
+    }
+    false
+}
+
+pub fn wake_up() -> Patch<i32> {
+    Patch::new("wake_up", "Wake the vacuum robot up").with(0, 2)
+}
+
+/// Works out a movement program from the camera, and returns the robot woken up and loaded
+/// with it, waiting only for the video feed answer.
+pub fn programmed_robot(input: &str) -> (Computer, String) {
+    let mut c: Computer = input.parse().unwrap();
+    let map = Grid::parse(&c.clone().run().output_as_string());
+    let path = trace_path(&map);
+    let program = compress(&path).expect("Path doesn't fit in three routines");
+    wake_up().apply(&mut c);
+    (c, program.as_program())
+}
+
 #[aoc(day17, part2)]
 pub fn p2(input: &str) -> i32 {
-    let mut c: Computer = input.parse().unwrap();
-    c.abs_store(0, 2);
-    let icode: &str = "
-A,B,A,C,A,B,C,A,B,C
-R,8,R,10,R,10
-R,4,R,8,R,10,R,12
-R,12,R,4,L,12,L,12
-n
-";
-    c.with_string_input(icode.trim_start())
+    let (mut c, program) = programmed_robot(input);
+    c.with_string_input(&(program + "n\n"))
         .run()
         .get_last_output()
 }
+
+/// Where the robot is in a video frame. `dir` is `None` once it has tumbled off the
+/// scaffold (shown as `X`).
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct RobotState {
+    pub pos: Point,
+    pub dir: Option<Dir>,
+}
+
+pub fn find_robot(frame: &Grid<char>) -> Option<RobotState> {
+    frame.cells().find_map(|(pos, &c)| match c {
+        'X' => Some(RobotState { pos, dir: None }),
+        _ => robot_dir(c).map(|d| RobotState { pos, dir: Some(d) }),
+    })
+}
+
+fn is_camera_char(c: char) -> bool {
+    c == '\n' || c == '.' || c == SCAFFOLD || c == 'X' || robot_dir(c).is_some()
+}
+
+/// The output of a run with the continuous video feed turned on.
+pub struct Video {
+    pub text: String,
+    pub dust: Option<i32>,
+}
+
+impl Video {
+    /// Runs `program` (as built by `Compressed::as_program`) with the video feed on.
+    pub fn record(mut c: Computer, program: &str) -> Self {
+        c.with_string_input(&(program.to_owned() + "y\n")).run();
+        let mut output = c.take_output();
+        let dust = output.last().cloned().filter(|&x| x > 255);
+        if dust.is_some() {
+            output.pop();
+        }
+        let text = output
+            .into_iter()
+            .map(|x| u8::try_from(x).map_or('?', char::from))
+            .collect();
+        Self { text, dust }
+    }
+    /// The raw text of each camera frame, skipping the movement prompts between them.
+    pub fn frame_texts(&self) -> impl Iterator<Item = &str> {
+        self.text
+            .split("\n\n")
+            .filter(|f| !f.trim().is_empty() && f.chars().all(is_camera_char))
+    }
+    pub fn frames(&self) -> impl Iterator<Item = HashMap<Point, char>> + '_ {
+        self.grids().map(|g| g.to_map())
+    }
+    /// As `frames`, without the hashing.
+    pub fn grids(&self) -> impl Iterator<Item = Grid<char>> + '_ {
+        self.frame_texts().map(Grid::parse)
+    }
+    /// The robot's position and heading in each frame.
+    pub fn robot_track(&self) -> Vec<Option<RobotState>> {
+        self.grids().map(|f| find_robot(&f)).collect()
+    }
+    /// The first frame in which the robot is missing or has tumbled off the scaffold.
+    pub fn first_fall(&self) -> Option<usize> {
+        self.robot_track()
+            .iter()
+            .position(|r| r.is_none_or(|r| r.dir.is_none()))
+    }
+    /// Plays the frames back in a terminal, clearing the screen between each one.
+    pub fn play(&self, frame_delay: std::time::Duration) {
+        for f in self.frame_texts() {
+            print!("\x1b[2J\x1b[H{}", f);
+            let _ = std::io::Write::flush(&mut std::io::stdout());
+            std::thread::sleep(frame_delay);
+        }
+    }
+}
+
+#[cfg(test)]
+const EXAMPLE: &str = "\
+#######...#####
+#.....#...#...#
+#.....#...#...#
+......#...#...#
+......#...###.#
+......#.....#.#
+^########...#.#
+......#.#...#.#
+......#########
+........#...#..
+....#########..
+....#...#......
+....#...#......
+....#...#......
+....#####......";
+
+#[test]
+pub fn d17p2_example() {
+    let path = trace_path(&Grid::parse(EXAMPLE));
+    assert_eq!(
+        routine_text(&path),
+        "R,8,R,8,R,4,R,4,R,8,L,6,L,2,R,4,R,4,R,8,R,8,R,8,L,6,L,2"
+    );
+    let c = compress(&path).unwrap();
+    assert!(c.routines.len() <= 3);
+    let expanded: Vec<Move> = c.main.iter().flat_map(|&i| c.routines[i].clone()).collect();
+    assert_eq!(expanded, path);
+    assert!(c.as_program().lines().all(|l| l.len() <= MAX_ROUTINE_LEN));
+}
+
+#[test]
+pub fn d17_video_frames() {
+    let text = "..#\n^##\n\nMain:\nFunction A:\n\n..#\n.#>\n\n..X\n.##\n\n";
+    let v = Video { text: text.to_string(), dust: None };
+    assert_eq!(v.frames().count(), 3);
+    assert_eq!(v.frames().next().unwrap()[&Point(0, 1)], '^');
+    assert_eq!(v.grids().nth(1).unwrap()[Point(2, 1)], '>');
+    assert_eq!(
+        v.robot_track(),
+        vec![
+            Some(RobotState { pos: Point(0, 1), dir: Some(Dir::D) }),
+            Some(RobotState { pos: Point(2, 1), dir: Some(Dir::R) }),
+            Some(RobotState { pos: Point(2, 0), dir: None }),
+        ]
+    );
+    assert_eq!(v.first_fall(), Some(2));
+}
+
+#[test]
+pub fn d17_video_record() {
+    //prompts, waits for the video answer, then sends two frames and the dust.
+    let out = |s: &str| s.bytes().map(|b| format!("output({}); ", b)).collect::<String>();
+    let src = format!(
+        "fn main() {{ {} var c = input(); while (c != 121) {{ c = input(); }} input(); {} output(1234); }}",
+        out("Main:\n"),
+        out("\n..#\n^##\n\n..#\n.#>\n\n..X\n.##\n\n")
+    );
+    let c = Computer::new(&crate::comp::compile::<i32>(&src).unwrap());
+    let v = Video::record(c, "A\nL,2\n\n\n");
+    assert_eq!(v.dust, Some(1234));
+    assert_eq!(v.frames().count(), 3);
+    assert_eq!(v.robot_track()[1], Some(RobotState { pos: Point(2, 1), dir: Some(Dir::R) }));
+    assert_eq!(v.first_fall(), Some(2));
+}
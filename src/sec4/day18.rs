@@ -1,10 +1,16 @@
 use std::collections::{HashMap, HashSet, BTreeSet};
-use crate::utils::points::{Point, as_point_map};
+use crate::utils::grid::Grid;
+use crate::utils::points::Point;
 use std::cmp::min;
-use std::hash::BuildHasher;
 
-pub fn search2<S: BuildHasher>(
-    map: &HashMap<Point, char, S>,
+/// Whether the map can be walked on at `c`: anything but walls, and the padding `Grid::parse`
+/// puts at the end of short lines.
+fn walkable(c: char) -> bool {
+    c == '.' || c == '@' || c.is_ascii_alphabetic()
+}
+
+pub fn search2(
+    map: &Grid<char>,
     start: Point,
 ) -> HashMap<Point, (char, usize, BTreeSet<char>)> {
     let mut points = std::collections::VecDeque::new();
@@ -18,8 +24,8 @@ pub fn search2<S: BuildHasher>(
             .iter()
             .filter(|&p| !been.contains(p))
             .for_each(|&p2| {
-                if let Some(here) = map.get(&p2) {
-                    if here != &'#' && (here == &'.' || !min_dist_map.contains_key(&p2))
+                if let Some(here) = map.get(p2) {
+                    if walkable(*here) && (here == &'.' || !min_dist_map.contains_key(&p2))
                     //&& (!here.is_uppercase() || keys.contains(&here.to_ascii_lowercase()))
                     {
                         let mut new_keys = keys.clone();
@@ -37,8 +43,8 @@ pub fn search2<S: BuildHasher>(
     min_dist_map
 }
 
-pub fn search<S : BuildHasher>(
-    map: &HashMap<Point, char, S>,
+pub fn search(
+    map: &Grid<char>,
     start: Point,
     keys: &BTreeSet<char>,
 ) -> HashMap<char, (usize, Point)> {
@@ -53,8 +59,8 @@ pub fn search<S : BuildHasher>(
             .iter()
             .filter(|&p| !been.contains(p))
             .for_each(|&p2| {
-                if let Some(here) = map.get(&p2) {
-                    if here != &'#'
+                if let Some(here) = map.get(p2) {
+                    if walkable(*here)
                         && (here == &'.' || !min_dist_map.contains_key(&p2))
                         && (!here.is_uppercase() || keys.contains(&here.to_ascii_lowercase()))
                     {
@@ -76,21 +82,21 @@ pub fn search<S : BuildHasher>(
 
 #[aoc(day18, part1)]
 pub fn p1(input: &str) -> usize {
-    let map = as_point_map(input);
-    let at_sym = *map.iter().find(|(_, &v)| v == '@').expect("No @").0;
+    let map = Grid::parse(input);
+    let at_sym = map.position(|&v| v == '@').expect("No @");
     solve(&map, &[at_sym])
 }
 
 #[aoc(day18, part2)]
 pub fn p2(input: &str) -> usize {
-    let mut map = as_point_map(input);
-    let at_sym = *map.iter().find(|(_, &v)| v == '@').expect("No @").0;
+    let mut map = Grid::parse(input);
+    let at_sym = map.position(|&v| v == '@').expect("No @");
 
-    map.insert(at_sym, '#');
-    map.insert(at_sym.up(), '#');
-    map.insert(at_sym.down(), '#');
-    map.insert(at_sym.left(), '#');
-    map.insert(at_sym.right(), '#');
+    map[at_sym] = '#';
+    map[at_sym.up()] = '#';
+    map[at_sym.down()] = '#';
+    map[at_sym.left()] = '#';
+    map[at_sym.right()] = '#';
     let points = [
         at_sym.up().left(),
         at_sym.up().right(),
@@ -101,18 +107,18 @@ pub fn p2(input: &str) -> usize {
 }
 
 type MapInfo = HashMap<Point, HashMap<Point,(char, usize, BTreeSet<char>)>>;
-pub fn solve<S : BuildHasher>(map: &HashMap<Point, char, S>, starts: &[Point]) -> usize {
+pub fn solve(map: &Grid<char>, starts: &[Point]) -> usize {
     let mut known_bests: HashMap<(Vec<Point>, BTreeSet<char>), usize> = HashMap::new();
     known_bests.insert((starts.to_vec(), BTreeSet::new()), 0);
     let locations: Vec<Point> = map
-        .iter()
-        .filter_map(|(&k, &v)| if v.is_lowercase() { Some(k) } else { None })
+        .cells()
+        .filter_map(|(k, &v)| if v.is_lowercase() { Some(k) } else { None })
         .chain(starts.iter().cloned())
         .collect();
     let info: MapInfo = locations
         .iter()
         .map(|&ap| {
-            let reachable = search2(&map, ap);
+            let reachable = search2(map, ap);
             (ap, reachable)
         })
         .collect();
@@ -176,4 +182,10 @@ mod test {
         assert_eq!(super::p1(&MAZ1), 132);
         assert_eq!(super::p1(&MAZ2), 136);
     }
+
+    #[test]
+    pub fn d18_short_lines() {
+        //the padding past the end of the second line would be a shortcut to b.
+        assert_eq!(super::p1("#######\n#a..@\n#.###b#\n#.....#\n#######\n"), 10);
+    }
 }
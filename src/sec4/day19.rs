@@ -1,27 +1,206 @@
-use crate::comp::Computer;
-use crate::utils::nums::unbounded_bin_search;
-use crate::utils::points::Point;
+use crate::comp::{par_map, Computer, DeviceError, Protocol};
+use crate::utils::points::{Aabb, Point};
 use itertools::Itertools;
+use std::collections::{BTreeMap, HashMap};
+
+/// The drone program takes a point and says whether it's in the tractor beam.
+pub struct BeamProbe;
+
+impl Protocol<isize> for BeamProbe {
+    type Command = Point;
+    type Response = bool;
+    const RESPONSE_LEN: usize = 1;
+    fn encode(&self, p: &Point) -> Vec<isize> {
+        vec![p.0, p.1]
+    }
+    fn decode(&self, o: &[isize]) -> Result<bool, DeviceError<isize>> {
+        match o[0] {
+            0 => Ok(false),
+            1 => Ok(true),
+            x => Err(DeviceError::UnknownCode(x)),
+        }
+    }
+}
 
 pub fn calc(c: &Computer<isize>, p: Point) -> isize {
-    let mut c = c.clone();
-    c.with_input(p.0).with_input(p.1).run().get_last_output()
+    let pulled = BeamProbe
+        .call(&mut c.clone(), &p)
+        .unwrap_or_else(|e| panic!("Drone error: {}", e));
+    pulled.into()
+}
+
+/// How many columns the beam can move per row, used to look for it near the emitter, before
+/// its edges have been found.
+const MAX_SLOPE: isize = 10;
+
+/// The tractor beam, as seen by a drone. Each point is only probed once, and the edges of each
+/// row are found by walking out from where the rows already traced say they should be.
+pub struct TractorBeam {
+    drone: Computer<isize>,
+    probes: HashMap<Point, bool>,
+    /// The first and last x in the beam on each row traced, if it's on that row at all.
+    rows: BTreeMap<isize, Option<(isize, isize)>>,
+}
+
+impl TractorBeam {
+    pub fn new(drone: Computer<isize>) -> Self {
+        Self {
+            drone,
+            probes: HashMap::new(),
+            rows: BTreeMap::new(),
+        }
+    }
+    pub fn probe(&mut self, p: Point) -> bool {
+        let drone = &self.drone;
+        *self.probes.entry(p).or_insert_with(|| calc(drone, p) == 1)
+    }
+    /// How many times the drone program has been run.
+    pub fn probe_count(&self) -> usize {
+        self.probes.len()
+    }
+    /// The nearest row to `y` that the beam's been found on, besides row 0.
+    fn anchor(&self, y: isize) -> Option<(isize, (isize, isize))> {
+        let found = |(&r, e): (&isize, &Option<(isize, isize)>)| e.map(|e| (r, e)).filter(|e| e.0 > 0);
+        let below = self.rows.range(..=y).rev().find_map(found);
+        let above = self.rows.range(y..).find_map(found);
+        below.into_iter().chain(above).min_by_key(|a| (a.0 - y).abs())
+    }
+    /// The first and last x in the beam on row `y`, if any.
+    pub fn row(&mut self, y: isize) -> Option<(isize, isize)> {
+        if let Some(&r) = self.rows.get(&y) {
+            return r;
+        }
+        let ans = match self.anchor(y) {
+            Some((ay, (s, e))) => self.trace(y, s * y / ay, e * y / ay),
+            None => {
+                let first = (0..=MAX_SLOPE * y).find(|&x| self.probe(Point(x, y)));
+                first.map(|s| (s, self.end_from(s, s, y)))
+            }
+        };
+        self.rows.insert(y, ans);
+        ans
+    }
+    /// Finds the beam on row `y` starting from guesses at its edges.
+    fn trace(&mut self, y: isize, start: isize, end: isize) -> Option<(isize, isize)> {
+        let mut s = start;
+        if self.probe(Point(s, y)) {
+            while s > 0 && self.probe(Point(s - 1, y)) {
+                s -= 1;
+            }
+        } else {
+            let right = (s + 1..=end.max(s) + 2).find(|&x| self.probe(Point(x, y)));
+            s = right.or_else(|| (0..s).rev().find(|&x| self.probe(Point(x, y))))?;
+            //came in from the right, so keep going to the left edge.
+            while s > 0 && self.probe(Point(s - 1, y)) {
+                s -= 1;
+            }
+        }
+        Some((s, self.end_from(s, end, y)))
+    }
+    /// The last x in the beam on row `y`, given it starts at `start`, and a guess.
+    fn end_from(&mut self, start: isize, guess: isize, y: isize) -> isize {
+        let mut e = guess.max(start);
+        if self.probe(Point(e, y)) {
+            while self.probe(Point(e + 1, y)) {
+                e += 1;
+            }
+        } else {
+            while e > start && !self.probe(Point(e, y)) {
+                e -= 1;
+            }
+        }
+        e
+    }
+    /// How many points in `area` are in the beam.
+    pub fn count_in(&mut self, area: Aabb) -> usize {
+        (area.bottom_left.1..=area.top_right.1)
+            .filter_map(|y| self.row(y))
+            .map(|(s, e)| {
+                let s = s.max(area.bottom_left.0);
+                let e = e.min(area.top_right.0);
+                (e - s + 1).max(0) as usize
+            })
+            .sum()
+    }
+    /// Whether a `w` by `h` rectangle fits in the beam with its top row on `y`, and if so,
+    /// where its top left corner is.
+    fn fit_at(&mut self, w: isize, h: isize, y: isize) -> Option<Point> {
+        let (_, top_end) = self.row(y)?;
+        let (bottom_start, _) = self.row(y + h - 1)?;
+        if top_end - bottom_start + 1 >= w {
+            Some(Point(bottom_start, y))
+        } else {
+            None
+        }
+    }
+    /// The top left corner of the first `w` by `h` rectangle that fits entirely in the beam,
+    /// going down from the row below the emitter, if one fits by row `max_y`.
+    pub fn first_fit(&mut self, w: isize, h: isize, max_y: isize) -> Option<Point> {
+        //rounding at the edges means a fit on one row doesn't always mean a fit on the next,
+        //so searching only gets close: gallop and bisect to a row that fits, then go back
+        //over the rows where the rounding could go either way.
+        if max_y < 1 {
+            return None;
+        }
+        let (mut lo, mut hi) = (0, 1);
+        while self.fit_at(w, h, hi).is_none() {
+            if hi == max_y {
+                return self.first_fit_near(w, h, hi);
+            }
+            lo = hi;
+            hi = (hi * 2).min(max_y);
+        }
+        while hi - lo > 1 {
+            let mid = (lo + hi) / 2;
+            if self.fit_at(w, h, mid).is_some() {
+                hi = mid;
+            } else {
+                lo = mid;
+            }
+        }
+        self.first_fit_near(w, h, hi)
+    }
+    /// The first fit on the rows up to `y` where rounding at the beam's edges could make a fit
+    /// come and go.
+    fn first_fit_near(&mut self, w: isize, h: isize, y: isize) -> Option<Point> {
+        //each edge is less than a column off the line it rounds, so whether a rectangle fits
+        //is only in doubt while it has under 2 columns of slack either way. That slack grows
+        //by the beam's width over its depth per row.
+        let (s, e) = self.row(y)?;
+        let window = 4 * y / (e - s + 1) + 1;
+        ((y - window).max(1)..=y).find_map(|r| self.fit_at(w, h, r))
+    }
 }
 
 #[aoc(day19, part1)]
-pub fn p1(input: &str) -> isize {
+pub fn p1(input: &str) -> usize {
+    let mut beam = TractorBeam::new(input.parse().unwrap());
+    beam.count_in(Aabb::new(Point(0, 0)).extend(Point(49, 49)))
+}
+#[aoc(day19, part1, brute_force)]
+pub fn p1a(input: &str) -> isize {
     let c: Computer<isize> = input.parse().unwrap();
-    (0..50)
+    let points: Vec<Point> = (0..50)
         .cartesian_product(0..50)
-        .map(|(x, y)| calc(&c, Point(x, y)))
-        .sum()
+        .map(|(x, y)| Point(x, y))
+        .collect();
+    par_map(
+        &c,
+        points,
+        |c, p| {
+            c.with_input(p.0).with_input(p.1);
+        },
+        |c, _| c.run().get_last_output(),
+    )
+    .iter()
+    .map(|x| x.1)
+    .sum()
 }
-#[aoc(day19, part2, bin_search_incorrect)]
-///This function wrongly assumes that the top edge of the tractor beam is on y=x.
+#[aoc(day19, part2)]
 pub fn p2(input: &str) -> isize {
-    let c: Computer<isize> = input.parse().unwrap();
-    let s = unbounded_bin_search(|s| calc(&c, Point(s + 99, s - 99)), 1);
-    s * 10000 + (s - 100)
+    let mut beam = TractorBeam::new(input.parse().unwrap());
+    let p = beam.first_fit(100, 100, 100_000).expect("Square never fits");
+    p.0 * 10000 + p.1
 }
 #[aoc(day19, part2, linear_search)]
 pub fn p2a(input: &str) -> isize {
@@ -37,3 +216,52 @@ pub fn p2a(input: &str) -> isize {
     }
     p.0 * 10000 + p.1
 }
+
+#[test]
+pub fn tractor_beam_tests() {
+    let prog = crate::comp::compile::<isize>(
+        "fn main() { var x = input(); var y = input(); output(2 * x >= y && 2 * x <= 3 * y); }",
+    )
+    .unwrap();
+    let drone = Computer::new(&prog);
+    let in_beam = |x: isize, y: isize| 2 * x >= y && 2 * x <= 3 * y;
+    let mut beam = TractorBeam::new(drone.clone());
+    let area = Aabb::new(Point(3, 5)).extend(Point(40, 60));
+    let expected = area.all_points().filter(|p| in_beam(p.0, p.1)).count();
+    assert_eq!(beam.count_in(area), expected);
+    assert!(beam.probe_count() < expected);
+    for &(w, h) in &[(1, 1), (3, 2), (2, 7), (10, 10)] {
+        let fits = |x: isize, y: isize| {
+            (0..w).all(|dx| (0..h).all(|dy| in_beam(x + dx, y + dy)))
+        };
+        let expected = (1..)
+            .find_map(|y| (0..=2 * y).find(|&x| fits(x, y)).map(|x| Point(x, y)))
+            .unwrap();
+        let mut beam = TractorBeam::new(drone.clone());
+        assert_eq!(beam.first_fit(w, h, 1000), Some(expected), "{}x{}", w, h);
+    }
+    assert_eq!(TractorBeam::new(drone).first_fit(10, 10, 5), None);
+
+    //a thin beam, where a 2x2 square fits on row 5, but not on the next two.
+    let prog = crate::comp::compile::<isize>(
+        "fn main() { var x = input(); var y = input(); output(6 * x >= y && 5 * x <= 2 * y); }",
+    )
+    .unwrap();
+    let in_beam = |x: isize, y: isize| 6 * x >= y && 5 * x <= 2 * y;
+    let fits = |x: isize, y: isize| (0..2).all(|dx| (0..2).all(|dy| in_beam(x + dx, y + dy)));
+    let fits_on = |y: isize| (0..=y).any(|x| fits(x, y));
+    assert!(fits_on(5) && !fits_on(6) && !fits_on(7) && fits_on(8));
+    assert!((1..5).all(|y| !fits_on(y)));
+    let mut beam = TractorBeam::new(Computer::new(&prog));
+    assert_eq!(beam.first_fit(2, 2, 100), Some(Point(1, 5)));
+
+    //further down, the search shouldn't need to trace every row above the fit.
+    let fits = |x: isize, y: isize| (0..30).all(|dx| (0..30).all(|dy| in_beam(x + dx, y + dy)));
+    let expected = (1..)
+        .find_map(|y| (0..=y).find(|&x| fits(x, y)).map(|x| Point(x, y)))
+        .unwrap();
+    let mut beam = TractorBeam::new(Computer::new(&prog));
+    assert_eq!(beam.first_fit(30, 30, 100_000), Some(expected));
+    //going down a row at a time takes 788.
+    assert!(beam.probe_count() < 400, "{} probes", beam.probe_count());
+}
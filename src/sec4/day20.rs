@@ -1,10 +1,10 @@
 use crate::utils::algorithms::{bfs_dist_all, to_lookup};
-use crate::utils::points::{as_point_map, Point};
+use crate::utils::grid::Grid;
+use crate::utils::points::Point;
 use crate::utils::prelude::HashMap;
 use itertools::Itertools;
 use pathfinding::directed::dijkstra::dijkstra;
 use std::hash::Hash;
-use std::convert::TryInto;
 
 #[derive(Debug, Eq, PartialEq, Hash, Clone)]
 pub struct Telepad {
@@ -21,12 +21,12 @@ pub fn p2(input: &str) -> u32 {
     solve(input, 1)
 }
 pub fn solve(input: &str, depth_step: isize) -> u32 {
-    let maz = as_point_map(input);
-    let width:isize = input.lines().nth(0).unwrap().len().try_into().unwrap();
-    let height:isize = input.lines().count().try_into().unwrap();
-    let telepads = to_lookup::<_, String, Telepad>(maz.iter().filter_map(|(p, c)| {
+    let maz = Grid::parse(input);
+    let width = maz.width() as isize;
+    let height = maz.height() as isize;
+    let telepads = to_lookup::<_, String, Telepad>(maz.cells().filter_map(|(p, c)| {
         if c.is_ascii_alphabetic() {
-            let n: Option<Vec<_>> = p.neighbours().iter().map(|x| maz.get(x)).collect();
+            let n: Option<Vec<_>> = p.neighbours().iter().map(|&x| maz.get(x)).collect();
             if let Some(n) = n {
                 let a = if n[0].is_ascii_alphabetic() && n[2] == &'.' {
                     Some((format!("{}{}", c, n[0]), p.down()))
@@ -80,7 +80,7 @@ pub fn solve(input: &str, depth_step: isize) -> u32 {
                 p.neighbours()
                     .iter()
                     .filter_map(|n|{
-                        if maz.get(n) == Some(&'.') {
+                        if maz.get(*n) == Some(&'.') {
                             Some((*n,1))
                         }else{
                             None
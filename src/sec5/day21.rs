@@ -1,8 +1,10 @@
 use crate::comp::Computer;
+use super::springscript::{Mode, Script};
+use super::springsynth::solve;
 
-pub fn go(input: &str, string_script: &str) -> Option<i32> {
+pub fn go(input: &str, script: &Script) -> Option<i32> {
     let mut c: Computer = input.parse().unwrap();
-    c.with_string_input(string_script)
+    c.with_string_input(&script.to_string())
         .run()
         .get_output()
         .iter()
@@ -14,29 +16,41 @@ pub fn go(input: &str, string_script: &str) -> Option<i32> {
         })
 }
 #[aoc(day21, part1)]
+pub fn p1_synth(input: &str) -> i32 {
+    solve(&input.parse().unwrap(), Mode::Walk).1
+}
+
+#[aoc(day21, part2)]
+pub fn p2_synth(input: &str) -> i32 {
+    solve(&input.parse().unwrap(), Mode::Run).1
+}
+
+#[aoc(day21, part1, hand_written)]
 pub fn p1(input: &str) -> i32 {
     //(a+b+c)*D
     go(
         input,
-        "\
+        &"\
 OR A J
 AND B J
 AND C J
 NOT J J
 AND D J
 WALK
-",
+"
+        .parse()
+        .unwrap(),
     )
     .unwrap_or(0)
 }
 
-#[aoc(day21, part2)]
+#[aoc(day21, part2, hand_written)]
 pub fn p2a(input: &str) -> i32 {
     //Jump if there's a hole and we can either step or jump after.
     //(a + b + c) & D & (E + H)
     go(
         input,
-        "\
+        &"\
 OR A J
 AND B J
 AND C J
@@ -46,7 +60,9 @@ OR E T
 OR H T
 AND T J
 RUN
-",
+"
+        .parse()
+        .unwrap(),
     )
     .unwrap_or(0)
 }
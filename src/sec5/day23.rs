@@ -1,17 +1,6 @@
-use std::collections::{VecDeque, HashMap};
 use crate::comp::Computer;
-
-pub fn run_comp(comp: &mut Computer<i64>, input: &[i64], send_q: &mut VecDeque<(i64, (i64, i64))>) -> bool {
-    input.iter().cloned().for_each(|x| {
-        comp.with_input(x);
-        comp.run_to_input();
-    });
-    let o = comp.take_output();
-    for c in o.chunks(3) {
-        send_q.push_back((c[0], (c[1], c[2])));
-    }
-    !o.is_empty()
-}
+use super::network::{Nat, Network, Schedule, Termination};
+pub use super::network::{Capture, Packet, Traffic, NAT};
 
 #[aoc(day23, part1)]
 pub fn p1(input: &str) -> i64 {
@@ -30,47 +19,49 @@ pub fn p2(input: &str) -> i64 {
 
 const COMP_COUNT: i64 = 50;
 
-pub fn run<T>(input: &str, mut send_nat_y: T) -> i64
+pub fn run<T>(input: &str, send_nat_y: T) -> i64
     where T: FnMut(i64) -> bool
 {
-    let master: Computer<i64> = input.parse().expect("Can't parse input");
-    let mut send_q: VecDeque<(i64, (i64, i64))> = VecDeque::new();
-    let mut comps: HashMap<_, _> = (0..COMP_COUNT).map(|a| {
-        let mut c = master.clone();
-        run_comp(&mut c, &[a], &mut send_q);
-        (a, c)
-    }).collect();
+    run_captured(input, send_nat_y).0
+}
 
-    let mut nat: Option<(i64, i64)> = None;
-    //loop until callback function tells us we're done.
-    loop {
-        //sending - send messages whilst there are any to send.
-        while !send_q.is_empty() {
-            let (ix, (x, y)) = send_q.pop_front().unwrap();
-            if ix == 255 {
-                nat = Some((x, y));
-            } else {
-                let mut c = comps.get_mut(&ix).unwrap();
-                run_comp(&mut c, &[x, y], &mut send_q);
-            }
-        }
-        //idle loop - try sending -1 to computers until someone sends something.
-        for mut c in comps.values_mut() {
-            if run_comp(&mut c, &[-1], &mut send_q) {
-                break;
-            }
-        }
-        //...if the idle loop is done, and still nothing in queue, send NAT value.
-        if send_q.is_empty() {
-            if let Some(n) = nat {
-                if send_nat_y(n.1) {
-                    break n.1;
-                }
-                send_q.push_back((0, n));
-                nat = None;
-            }else {
-                panic!("Network quiet, nothing to send");
-            }
-        }
+/// As `run`, also returning a capture of all the network traffic.
+pub fn run_captured<T>(input: &str, send_nat_y: T) -> (i64, Capture)
+    where T: FnMut(i64) -> bool
+{
+    let master: Computer<i64> = input.parse().expect("Can't parse input");
+    let (t, cap) = Network::new(master, COMP_COUNT)
+        .handler(NAT, Nat::new(send_nat_y))
+        .schedule(Schedule::Immediate)
+        .run();
+    match t {
+        Termination::Stopped { value, .. } => (value, cap),
+        t => panic!("Network stopped without an answer: {:?}", t),
     }
 }
+
+#[test]
+pub fn capture_tests() {
+    use crate::comp::{compile, ImageFormat};
+    //0 sends to 1, which passes everything on to the NAT, which wakes 0, which sends it again.
+    let prog: Vec<i64> = compile(super::network::TEST_NIC).unwrap();
+    let prog = String::from_utf8(ImageFormat::Comma.encode(&prog).unwrap()).unwrap();
+    let mut last = None;
+    let (ans, cap) = run_captured(&prog, |y| last.replace(y) == Some(y));
+    assert_eq!(ans, 20);
+    assert_eq!(cap.rounds, 2);
+    let hops: Vec<_> = cap.packets.iter().map(|p| (p.round, p.source, p.dest)).collect();
+    assert_eq!(hops, vec![(0, 0, 1), (1, 1, NAT), (1, NAT, 0), (2, 0, 1), (2, 1, NAT)]);
+    assert_eq!(cap.nat_resends, vec![Packet { round: 1, source: NAT, dest: 0, x: 10, y: 20 }]);
+    let traffic = cap.node_traffic();
+    assert_eq!(traffic[&0], Traffic { sent: 2, received: 1 });
+    assert_eq!(traffic[&NAT], Traffic { sent: 1, received: 2 });
+    let mut log = Vec::new();
+    cap.write_log(&mut log).unwrap();
+    let log = String::from_utf8(log).unwrap();
+    assert!(log.starts_with("0 0 -> 1 10 20\n1 1 -> 255 10 20\n"));
+    assert!(log.contains("# NAT resent (10, 20) in round 1\n"));
+
+    let cap = Capture { idle_rounds: vec![2, 3, 4, 7, 9, 10], ..Capture::default() };
+    assert_eq!(cap.idle_periods(), vec![(2, 3), (7, 1), (9, 2)]);
+}
@@ -1,32 +1,39 @@
 use std::collections::HashSet;
-use num::pow;
+use crate::utils::grid::Grid;
 use crate::utils::points::Point;
-use std::convert::TryInto;
 use itertools::iterate;
 use crate::utils::algorithms::{automata_step, automata_step_mut};
-use std::hash::BuildHasher;
 
+/// Whether there's a bug on each tile.
 #[aoc_generator(day24)]
-pub fn gen(input: &str) -> HashSet<Point> {
-    let hm = crate::utils::points::as_point_map(input);
-    hm.iter().filter_map(|(a, b)| if b == &'#' { Some(*a) } else { None }).collect()
+pub fn gen(input: &str) -> Grid<bool> {
+    Grid::parse(input).map(|&c| c == '#')
+}
+
+/// The next minute on a single level.
+pub fn flat_step(g: &Grid<bool>) -> Grid<bool> {
+    Grid::from_fn(g.width(), g.height(), |p| {
+        lives(g[p], g.neighbours(p).filter(|&n| g[n]).count())
+    })
 }
 
 #[aoc(day24, part1)]
-pub fn p1<S>(input: &HashSet<Point, S>) -> usize
-    where S: BuildHasher + Default + Clone
-{
+pub fn p1(input: &Grid<bool>) -> usize {
     let mut seen = HashSet::new();
-    iterate(input.clone(),
-            |g| automata_step(g, flat_neighbours, lives))
+    iterate(input.clone(), flat_step)
         .map(|x| biodiversity(&x))
         .filter(|&x| !seen.insert(x))
         .nth(0).unwrap()
 }
 
+/// The bugs on the middle level, to start off the recursive levels.
+fn levels(input: &Grid<bool>) -> HashSet<(Point, i32)> {
+    input.cells().filter(|c| *c.1).map(|c| (c.0, 0)).collect()
+}
+
 #[aoc(day24, part2, mutate)]
-pub fn p2m<S: BuildHasher + Default>(input: &HashSet<Point, S>) -> usize {
-    let mut g: HashSet<(Point, i32)> = input.iter().map(|a| (*a, 0)).collect();
+pub fn p2m(input: &Grid<bool>) -> usize {
+    let mut g = levels(input);
     for _ in 0..200 {
         automata_step_mut(&mut g, recur_neighbours, lives);
     }
@@ -34,8 +41,8 @@ pub fn p2m<S: BuildHasher + Default>(input: &HashSet<Point, S>) -> usize {
 }
 
 #[aoc(day24, part2, recreate)]
-pub fn p2c<S: BuildHasher + Default>(input: &HashSet<Point, S>) -> usize {
-    let mut g: HashSet<(Point, i32)> = input.iter().map(|a| (*a, 0)).collect();
+pub fn p2c(input: &Grid<bool>) -> usize {
+    let mut g = levels(input);
     for _ in 0..200 {
         g = automata_step(&g, recur_neighbours, lives);
     }
@@ -80,12 +87,8 @@ pub fn d24p2() {
 }
 
 
-pub fn biodiversity<S>(g: &HashSet<Point, S>) -> usize
-    where S: BuildHasher
-{
-    g.iter().map(|&p| {
-        pow(2, (p.0 + p.1 * 5).try_into().unwrap())
-    }).sum()
+pub fn biodiversity(g: &Grid<bool>) -> usize {
+    g.rows().flatten().enumerate().filter(|c| *c.1).map(|(ix, _)| 1 << ix).sum()
 }
 
 #[test]
@@ -1,3 +1,4 @@
+use crate::comp::{Backtrace, BridgeMode, Server};
 use crate::utils::prelude::*;
 use std::io::{stdin, stdout, Write};
 
@@ -37,8 +38,27 @@ pub fn p1(input:&str) -> String {
     c.run_to_input();
     c.output_as_string()
 }
+
+/// Plays the solution with call tracing on, and returns the program's call graph.
+pub fn call_graph(input: &str) -> String {
+    let mut c: Computer<i64> = input.parse().unwrap();
+    c.with_call_trace().with_string_input(SOLUTION);
+    c.run_to_input();
+    c.call_trace().unwrap().call_graph_dot()
+}
+
+/// Hosts the game on a loopback `addr` (say `127.0.0.1:2525`), for playing with `nc` or a
+/// `Client`.
+pub fn serve(input: &str, addr: &str) -> std::io::Result<()> {
+    let c: Computer<i64> = input.parse().unwrap();
+    Server::bind(addr, c, BridgeMode::Ascii)?.serve()
+}
+
+/// Play the game at the terminal. An empty line undoes the last command, and `bt` prints
+/// the program's call stack.
 pub fn interactive(input: &str) -> i64 {
     let mut c: Computer<i64> = input.parse().unwrap();
+    c.with_call_trace();
     let mut save = c.clone();
     c.run_to_input();
     loop {
@@ -50,7 +70,9 @@ pub fn interactive(input: &str) -> i64 {
         stdin().read_line(&mut s).expect("Bad input");
         let r = s.find("\r").unwrap();
         s.remove(r);
-        if s.trim().is_empty() {
+        if s.trim() == "bt" {
+            println!("{}", Backtrace::of(&c).unwrap());
+        } else if s.trim().is_empty() {
             c = save.clone();
         } else {
             save = c.clone();
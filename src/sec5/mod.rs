@@ -3,3 +3,6 @@ pub mod day22;
 pub mod day23;
 pub mod day24;
 pub mod day25;
+pub mod springscript;
+pub mod springsynth;
+pub mod network;
@@ -0,0 +1,524 @@
+use crate::comp::{Computer, StopReason};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::fmt;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// The NAT's address.
+pub const NAT: i64 = 255;
+
+/// One packet, as seen on the wire.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Packet {
+    /// The scheduler round it was sent in; round 0 is boot.
+    pub round: usize,
+    pub source: i64,
+    pub dest: i64,
+    pub x: i64,
+    pub y: i64,
+}
+
+impl fmt::Display for Packet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} -> {} {} {}", self.round, self.source, self.dest, self.x, self.y)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Traffic {
+    pub sent: usize,
+    pub received: usize,
+}
+
+/// Everything that happened on the network during a run.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Capture {
+    /// Every packet, in the order it was sent.
+    pub packets: Vec<Packet>,
+    /// Rounds in which no computer sent anything.
+    pub idle_rounds: Vec<usize>,
+    /// The packets handlers sent to wake the network when it went quiet, like the NAT's.
+    pub nat_resends: Vec<Packet>,
+    pub rounds: usize,
+}
+
+impl Capture {
+    /// Packets sent and received, by address.
+    pub fn node_traffic(&self) -> BTreeMap<i64, Traffic> {
+        let mut ans: BTreeMap<i64, Traffic> = BTreeMap::new();
+        for p in &self.packets {
+            ans.entry(p.source).or_default().sent += 1;
+            ans.entry(p.dest).or_default().received += 1;
+        }
+        ans
+    }
+    /// Runs of consecutive idle rounds, as `(first round, length)`.
+    pub fn idle_periods(&self) -> Vec<(usize, usize)> {
+        let mut ans: Vec<(usize, usize)> = Vec::new();
+        for &r in &self.idle_rounds {
+            match ans.last_mut() {
+                Some((start, len)) if *start + *len == r => *len += 1,
+                _ => ans.push((r, 1)),
+            }
+        }
+        ans
+    }
+    pub fn summary(&self) -> String {
+        let mut ans = format!("{} packets in {} rounds\n", self.packets.len(), self.rounds);
+        ans.push_str("node  sent  received\n");
+        for (a, t) in self.node_traffic() {
+            ans.push_str(&format!("{:4}  {:4}  {:8}\n", a, t.sent, t.received));
+        }
+        let idle: Vec<String> = self
+            .idle_periods()
+            .iter()
+            .map(|&(s, l)| format!("{}-{}", s, s + l - 1))
+            .collect();
+        ans.push_str(&format!("idle rounds: {}\n", idle.join(", ")));
+        for p in &self.nat_resends {
+            ans.push_str(&format!("NAT resent ({}, {}) in round {}\n", p.x, p.y, p.round));
+        }
+        ans
+    }
+    /// Writes one `round source -> dest x y` line per packet, then the summary as `#` comments.
+    pub fn write_log<W: Write>(&self, mut out: W) -> io::Result<()> {
+        for p in &self.packets {
+            writeln!(out, "{}", p)?;
+        }
+        for l in self.summary().lines() {
+            writeln!(out, "# {}", l)?;
+        }
+        Ok(())
+    }
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.write_log(io::BufWriter::new(std::fs::File::create(path)?))
+    }
+}
+
+
+/// What a handler wants done after seeing a packet or the network going quiet.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Outbox {
+    sent: Vec<(i64, i64, i64)>,
+    stop: Option<i64>,
+}
+
+impl Outbox {
+    pub fn send(&mut self, dest: i64, x: i64, y: i64) {
+        self.sent.push((dest, x, y));
+    }
+    /// Stops the network, with `value` as the result.
+    pub fn stop(&mut self, value: i64) {
+        self.stop = Some(value);
+    }
+}
+
+/// Something other than a computer at a network address.
+pub trait Handler {
+    fn receive(&mut self, packet: &Packet, out: &mut Outbox);
+    /// Called when the network goes quiet. If no handler sends anything, the run ends.
+    fn idle(&mut self, _out: &mut Outbox) {}
+}
+
+/// The puzzle's NAT, which lives at `NAT`: remembers the last packet it got, and sends it to address 0 when the
+/// network goes quiet, unless `stop_on` says to stop with its `y` instead.
+pub struct Nat<F> {
+    last: Option<(i64, i64)>,
+    stop_on: F,
+}
+
+impl<F: FnMut(i64) -> bool> Nat<F> {
+    pub fn new(stop_on: F) -> Self {
+        Self { last: None, stop_on }
+    }
+}
+
+impl<F: FnMut(i64) -> bool> Handler for Nat<F> {
+    fn receive(&mut self, packet: &Packet, _out: &mut Outbox) {
+        self.last = Some((packet.x, packet.y));
+    }
+    fn idle(&mut self, out: &mut Outbox) {
+        if let Some((x, y)) = self.last.take() {
+            if (self.stop_on)(y) {
+                out.stop(y);
+            } else {
+                out.send(0, x, y);
+            }
+        }
+    }
+}
+
+/// Writes each packet it gets as a line of text, and drops it.
+pub struct Logger<W>(pub W);
+
+impl<W: Write> Handler for Logger<W> {
+    fn receive(&mut self, packet: &Packet, _out: &mut Outbox) {
+        //a log that can't be written shouldn't change how the network behaves.
+        writeln!(self.0, "{}", packet).ok();
+    }
+}
+
+/// Passes each packet it gets on to another address.
+pub struct Bridge(pub i64);
+
+impl Handler for Bridge {
+    fn receive(&mut self, packet: &Packet, out: &mut Outbox) {
+        out.send(self.0, packet.x, packet.y);
+    }
+}
+
+/// What computers get when they try to read a packet and have none waiting.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IdlePolicy {
+    /// Give them this value, like the puzzle's -1.
+    Feed(i64),
+    /// Don't run them.
+    Skip,
+}
+
+/// The order computers run in, in each round.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Schedule {
+    /// In address order, each reading at most one packet.
+    RoundRobin,
+    /// In a random order from the seed, shuffled each round, each reading at most one packet.
+    Random(u64),
+    /// In address order, each reading packets until it has none waiting.
+    RunUntilBlocked,
+    /// Packets are read as soon as they're sent, oldest first, then idle computers are polled
+    /// in address order until one sends something. The network is quiet when the poll sends
+    /// nothing. This is how day 23 has always run.
+    Immediate,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Termination {
+    /// The handler at `by` stopped the network.
+    Stopped { by: i64, value: i64 },
+    /// Nothing was sent for long enough, and no handler woke anything.
+    Quiet,
+    /// Every computer halted.
+    Halted,
+    /// A packet was sent to an address with nothing there.
+    Undeliverable(Packet),
+    BadInstruction { node: i64, ip: isize },
+    RoundLimit,
+}
+
+/// Copies of one program at addresses `0..nodes`, plus handlers at other addresses, passing
+/// `(dest, x, y)` packets. Each computer is booted with its address, then they run in rounds;
+/// packets are delivered as soon as they're sent.
+pub struct Network<'a> {
+    program: Computer<i64>,
+    nodes: i64,
+    handlers: BTreeMap<i64, Box<dyn Handler + 'a>>,
+    idle: IdlePolicy,
+    schedule: Schedule,
+    quiet_after: usize,
+    max_rounds: Option<usize>,
+}
+
+/// The state of the computers, and what's been seen on the wire.
+struct Wire {
+    comps: Vec<Computer<i64>>,
+    /// Packets waiting for each computer, with their index in the capture.
+    queues: Vec<VecDeque<(usize, i64, i64)>>,
+    /// Output which isn't yet a whole packet.
+    pending: Vec<Vec<i64>>,
+    capture: Capture,
+}
+
+impl<'a> Network<'a> {
+    /// With no handlers, `-1` fed to idle computers, round robin scheduling, and the network
+    /// quiet after one round where nothing is sent.
+    pub fn new(program: Computer<i64>, nodes: i64) -> Self {
+        Self {
+            program,
+            nodes,
+            handlers: BTreeMap::new(),
+            idle: IdlePolicy::Feed(-1),
+            schedule: Schedule::RoundRobin,
+            quiet_after: 1,
+            max_rounds: None,
+        }
+    }
+    pub fn handler<H: Handler + 'a>(mut self, addr: i64, h: H) -> Self {
+        assert!(addr < 0 || addr >= self.nodes, "Address {} has a computer", addr);
+        self.handlers.insert(addr, Box::new(h));
+        self
+    }
+    pub fn idle_policy(mut self, idle: IdlePolicy) -> Self {
+        self.idle = idle;
+        self
+    }
+    pub fn schedule(mut self, schedule: Schedule) -> Self {
+        self.schedule = schedule;
+        self
+    }
+    /// How many rounds in a row must pass with nothing sent before the network is quiet.
+    pub fn quiet_after(mut self, rounds: usize) -> Self {
+        self.quiet_after = rounds.max(1);
+        self
+    }
+    pub fn max_rounds(mut self, rounds: usize) -> Self {
+        self.max_rounds = Some(rounds);
+        self
+    }
+    /// Delivers packets, and whatever handlers send in response.
+    fn route(&mut self, wire: &mut Wire, packets: Vec<Packet>) -> Result<(), Termination> {
+        let mut todo: VecDeque<Packet> = packets.into();
+        while let Some(p) = todo.pop_front() {
+            let ix = wire.capture.packets.len();
+            wire.capture.packets.push(p);
+            if (0..self.nodes).contains(&p.dest) {
+                wire.queues[p.dest as usize].push_back((ix, p.x, p.y));
+                continue;
+            }
+            let h = self
+                .handlers
+                .get_mut(&p.dest)
+                .ok_or(Termination::Undeliverable(p))?;
+            let mut out = Outbox::default();
+            h.receive(&p, &mut out);
+            if let Some(value) = out.stop {
+                return Err(Termination::Stopped { by: p.dest, value });
+            }
+            todo.extend(out.sent.into_iter().map(|(dest, x, y)| Packet {
+                round: p.round,
+                source: p.dest,
+                dest,
+                x,
+                y,
+            }));
+        }
+        Ok(())
+    }
+    /// Runs computer `a` on `input`, and sends any complete packets it has output.
+    fn step(&mut self, wire: &mut Wire, a: i64, input: Vec<i64>) -> Result<(), Termination> {
+        let c = &mut wire.comps[a as usize];
+        c.give_input(input);
+        if let StopReason::BadInstruction(ip) = c.run_to_breakpoint(&HashSet::new()) {
+            return Err(Termination::BadInstruction { node: a, ip });
+        }
+        let out = &mut wire.pending[a as usize];
+        out.extend(c.take_output());
+        let whole = out.len() - out.len() % 3;
+        let round = wire.capture.rounds;
+        let packets = out
+            .drain(..whole)
+            .collect::<Vec<_>>()
+            .chunks(3)
+            .map(|v| Packet {
+                round,
+                source: a,
+                dest: v[0],
+                x: v[1],
+                y: v[2],
+            })
+            .collect();
+        self.route(wire, packets)
+    }
+    /// Gives computer `a` its turn in a round.
+    fn turn(&mut self, wire: &mut Wire, a: i64) -> Result<(), Termination> {
+        let mut fed = false;
+        while let Some((_, x, y)) = wire.queues[a as usize].pop_front() {
+            fed = true;
+            self.step(wire, a, vec![x, y])?;
+            if self.schedule != Schedule::RunUntilBlocked || wire.comps[a as usize].is_halted() {
+                return Ok(());
+            }
+        }
+        match self.idle {
+            IdlePolicy::Feed(v) if !fed => self.step(wire, a, vec![v]),
+            _ => Ok(()),
+        }
+    }
+    /// Has every waiting packet read, oldest first, including any sent in response.
+    fn deliver_all(&mut self, wire: &mut Wire) -> Result<(), Termination> {
+        loop {
+            let next = (0..self.nodes)
+                .filter_map(|a| wire.queues[a as usize].front().map(|q| (q.0, a)))
+                .min();
+            let a = match next {
+                Some((_, a)) => a,
+                None => return Ok(()),
+            };
+            let (_, x, y) = wire.queues[a as usize].pop_front().unwrap();
+            if !wire.comps[a as usize].is_halted() {
+                self.step(wire, a, vec![x, y])?;
+            }
+        }
+    }
+    /// Feeds idle computers in `order` until one sends something.
+    fn poll(&mut self, wire: &mut Wire, order: &[i64]) -> Result<(), Termination> {
+        if let IdlePolicy::Feed(v) = self.idle {
+            let sent_before = wire.capture.packets.len();
+            for &a in order {
+                if !wire.comps[a as usize].is_halted() {
+                    self.step(wire, a, vec![v])?;
+                }
+                if wire.capture.packets.len() > sent_before {
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+    /// Lets each handler act on the network being quiet.
+    fn wake(&mut self, wire: &mut Wire) -> Result<bool, Termination> {
+        let round = wire.capture.rounds;
+        let mut sent = Vec::new();
+        for (&by, h) in self.handlers.iter_mut() {
+            let mut out = Outbox::default();
+            h.idle(&mut out);
+            if let Some(value) = out.stop {
+                return Err(Termination::Stopped { by, value });
+            }
+            sent.extend(out.sent.into_iter().map(|(dest, x, y)| Packet {
+                round,
+                source: by,
+                dest,
+                x,
+                y,
+            }));
+        }
+        wire.capture.nat_resends.extend_from_slice(&sent);
+        let woke = !sent.is_empty();
+        self.route(wire, sent)?;
+        Ok(woke)
+    }
+    /// Runs rounds until the network ends by itself (`Ok`) or something goes wrong (`Err`).
+    fn run_rounds(&mut self, wire: &mut Wire) -> Result<Termination, Termination> {
+        let mut order: Vec<i64> = (0..self.nodes).collect();
+        let mut rng = match self.schedule {
+            Schedule::Random(seed) => Some(StdRng::seed_from_u64(seed)),
+            _ => None,
+        };
+        for &a in &order {
+            self.step(wire, a, vec![a])?;
+        }
+        let mut quiet = 0;
+        loop {
+            if wire.comps.iter().all(Computer::is_halted) {
+                return Ok(Termination::Halted);
+            }
+            if self.max_rounds.is_some_and(|m| wire.capture.rounds >= m) {
+                return Ok(Termination::RoundLimit);
+            }
+            wire.capture.rounds += 1;
+            if let Some(rng) = &mut rng {
+                order.shuffle(rng);
+            }
+            let sent_before = wire.capture.packets.len();
+            let is_quiet = if self.schedule == Schedule::Immediate {
+                self.deliver_all(wire)?;
+                let polled = wire.capture.packets.len();
+                self.poll(wire, &order)?;
+                wire.capture.packets.len() == polled
+            } else {
+                for &a in &order {
+                    if !wire.comps[a as usize].is_halted() {
+                        self.turn(wire, a)?;
+                    }
+                }
+                wire.capture.packets.len() == sent_before && wire.queues.iter().all(VecDeque::is_empty)
+            };
+            if wire.capture.packets.len() == sent_before {
+                wire.capture.idle_rounds.push(wire.capture.rounds);
+            }
+            if !is_quiet {
+                quiet = 0;
+                continue;
+            }
+            quiet += 1;
+            if quiet >= self.quiet_after {
+                if !self.wake(wire)? {
+                    return Ok(Termination::Quiet);
+                }
+                quiet = 0;
+            }
+        }
+    }
+    /// Runs the network until something stops it, capturing all the traffic.
+    pub fn run(mut self) -> (Termination, Capture) {
+        let mut wire = Wire {
+            comps: vec![self.program.clone(); self.nodes as usize],
+            queues: vec![VecDeque::new(); self.nodes as usize],
+            pending: vec![Vec::new(); self.nodes as usize],
+            capture: Capture::default(),
+        };
+        let t = self.run_rounds(&mut wire).unwrap_or_else(|t| t);
+        (t, wire.capture)
+    }
+}
+
+/// 0 sends to 1, which passes everything on to 255, which (as a NAT) wakes 0 to send it again.
+#[cfg(test)]
+pub(crate) const TEST_NIC: &str = "fn main() {
+    var me = input();
+    if (me == 0) { output(1); output(10); output(20); }
+    while (1) {
+        var x = input();
+        if (x != -1) {
+            var y = input();
+            if (me == 0) { output(1); } else { output(255); }
+            output(x); output(y);
+        }
+    }
+}";
+
+#[cfg(test)]
+fn test_nic() -> Computer<i64> {
+    Computer::new(&crate::comp::compile::<i64>(TEST_NIC).unwrap())
+}
+
+#[test]
+pub fn network_tests() {
+    let schedules = [
+        Schedule::RoundRobin,
+        Schedule::Random(1),
+        Schedule::Random(2),
+        Schedule::RunUntilBlocked,
+        Schedule::Immediate,
+    ];
+    for &s in &schedules {
+        let mut last = None;
+        let (t, _) = Network::new(test_nic(), 3)
+            .handler(NAT, Nat::new(|y| last.replace(y) == Some(y)))
+            .schedule(s)
+            .run();
+        assert_eq!(t, Termination::Stopped { by: NAT, value: 20 }, "{:?}", s);
+    }
+    //round robin only passes a packet on a round after getting it, and the NAT only wakes
+    //things after a round where nothing was sent.
+    let mut last = None;
+    let (_, cap) = Network::new(test_nic(), 2)
+        .handler(NAT, Nat::new(|y| last.replace(y) == Some(y)))
+        .run();
+    assert_eq!(cap.rounds, 4);
+    let hops: Vec<_> = cap.packets.iter().map(|p| (p.round, p.source, p.dest)).collect();
+    assert_eq!(hops, vec![(0, 0, 1), (1, 1, NAT), (2, NAT, 0), (3, 0, 1), (3, 1, NAT)]);
+    assert_eq!(cap.idle_periods(), vec![(2, 1), (4, 1)]);
+    //with nothing at 255, the first packet there is lost.
+    let (t, cap) = Network::new(test_nic(), 2).run();
+    assert!(matches!(t, Termination::Undeliverable(p) if p.dest == NAT));
+    assert_eq!(cap.packets.len(), 2);
+    //a logger swallows packets, so the network goes quiet.
+    let mut log = Vec::new();
+    let (t, _) = Network::new(test_nic(), 2)
+        .handler(NAT, Logger(&mut log))
+        .idle_policy(IdlePolicy::Skip)
+        .run();
+    assert_eq!(t, Termination::Quiet);
+    assert_eq!(String::from_utf8(log).unwrap(), "1 1 -> 255 10 20\n");
+    //bridging 255 back to 0 keeps it busy forever.
+    let (t, cap) = Network::new(test_nic(), 2)
+        .handler(NAT, Bridge(0))
+        .max_rounds(10)
+        .run();
+    assert_eq!(t, Termination::RoundLimit);
+    assert!(cap.idle_rounds.is_empty());
+}
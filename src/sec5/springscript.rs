@@ -0,0 +1,316 @@
+use std::fmt;
+use std::str::FromStr;
+
+pub const MAX_INSTRUCTIONS: usize = 15;
+const WALK_RANGE: usize = 4;
+const RUN_RANGE: usize = 9;
+const JUMP_LEN: usize = 4;
+
+/// A springscript register. `A` to `I` are the read-only hull sensors, `T` and `J` are writable.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, PartialOrd, Ord)]
+pub enum Reg {
+    Sensor(u8),
+    T,
+    J,
+}
+
+impl Reg {
+    pub fn sensor(c: char) -> Option<Self> {
+        if ('A'..='I').contains(&c) {
+            Some(Self::Sensor(c as u8 - b'A'))
+        } else {
+            None
+        }
+    }
+    pub fn is_writable(self) -> bool {
+        self == Self::T || self == Self::J
+    }
+}
+
+impl FromStr for Reg {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut cs = s.chars();
+        match (cs.next(), cs.next()) {
+            (Some('T'), None) => Ok(Self::T),
+            (Some('J'), None) => Ok(Self::J),
+            (Some(c), None) => Self::sensor(c).ok_or(()),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for Reg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Sensor(n) => write!(f, "{}", (b'A' + n) as char),
+            Self::T => write!(f, "T"),
+            Self::J => write!(f, "J"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum OpKind {
+    And,
+    Or,
+    Not,
+}
+
+impl fmt::Display for OpKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Self::And => "AND",
+            Self::Or => "OR",
+            Self::Not => "NOT",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Instruction {
+    pub op: OpKind,
+    pub src: Reg,
+    pub dst: Reg,
+}
+
+impl Instruction {
+    pub fn new(op: OpKind, src: Reg, dst: Reg) -> Self {
+        Self { op, src, dst }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {}", self.op, self.src, self.dst)
+    }
+}
+
+/// `WALK` lets the droid see 4 tiles ahead (`A`-`D`), `RUN` 9 (`A`-`I`).
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Mode {
+    Walk,
+    Run,
+}
+
+impl Mode {
+    pub fn sensor_range(self) -> usize {
+        match self {
+            Self::Walk => WALK_RANGE,
+            Self::Run => RUN_RANGE,
+        }
+    }
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Walk => write!(f, "WALK"),
+            Self::Run => write!(f, "RUN"),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum ScriptError {
+    /// A line (1-based) that isn't a springscript instruction.
+    BadLine(usize, String),
+    /// An instruction (1-based) writing to a sensor.
+    NotWritable(usize, Reg),
+    /// An instruction (1-based) reading a sensor the mode doesn't have.
+    SensorUnavailable(usize, Reg, Mode),
+    TooLong(usize),
+    MissingMode,
+    /// A line (1-based) after the `WALK` or `RUN` which should have been the last.
+    AfterMode(usize, String),
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BadLine(l, s) => write!(f, "line {}: can't parse '{}'", l, s),
+            Self::NotWritable(l, r) => write!(f, "instruction {}: can't write to {}", l, r),
+            Self::SensorUnavailable(l, r, m) => {
+                write!(f, "instruction {}: sensor {} isn't available in {} mode", l, r, m)
+            }
+            Self::TooLong(n) => write!(
+                f,
+                "{} instructions, but only {} fit in memory",
+                n, MAX_INSTRUCTIONS
+            ),
+            Self::MissingMode => write!(f, "script doesn't end with WALK or RUN"),
+            Self::AfterMode(l, s) => write!(f, "line {}: '{}' after the end of the script", l, s),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Script {
+    pub instructions: Vec<Instruction>,
+    pub mode: Mode,
+}
+
+impl Script {
+    pub fn new(instructions: Vec<Instruction>, mode: Mode) -> Self {
+        Self { instructions, mode }
+    }
+    fn checked(instructions: Vec<Instruction>, mode: Mode) -> Result<Self, ScriptError> {
+        let s = Self::new(instructions, mode);
+        s.validate()?;
+        Ok(s)
+    }
+    /// Checks the script would be accepted by the springdroid.
+    pub fn validate(&self) -> Result<(), ScriptError> {
+        if self.instructions.len() > MAX_INSTRUCTIONS {
+            return Err(ScriptError::TooLong(self.instructions.len()));
+        }
+        for (ix, i) in self.instructions.iter().enumerate() {
+            if !i.dst.is_writable() {
+                return Err(ScriptError::NotWritable(ix + 1, i.dst));
+            }
+            if let Reg::Sensor(n) = i.src {
+                if usize::from(n) >= self.mode.sensor_range() {
+                    return Err(ScriptError::SensorUnavailable(ix + 1, i.src, self.mode));
+                }
+            }
+        }
+        Ok(())
+    }
+    /// Runs the script against sensor readings (`true` is ground), returning whether to jump.
+    pub fn should_jump(&self, sensors: &[bool]) -> bool {
+        let mut t = false;
+        let mut j = false;
+        for i in &self.instructions {
+            let src = match i.src {
+                Reg::Sensor(n) => sensors.get(usize::from(n)).cloned().unwrap_or(true),
+                Reg::T => t,
+                Reg::J => j,
+            };
+            let dst = if i.dst == Reg::T { &mut t } else { &mut j };
+            *dst = match i.op {
+                OpKind::And => *dst && src,
+                OpKind::Or => *dst || src,
+                OpKind::Not => !src,
+            };
+        }
+        j
+    }
+    /// Walks the droid along `hull`, starting on tile 0. Returns the tile it falls into,
+    /// if it does.
+    pub fn simulate(&self, hull: &[bool]) -> Result<(), usize> {
+        let range = self.mode.sensor_range();
+        let ground = |ix: usize| hull.get(ix).cloned().unwrap_or(true);
+        let mut pos = 0;
+        while pos < hull.len() {
+            let sensors: Vec<bool> = (1..=range).map(|d| ground(pos + d)).collect();
+            pos += if self.should_jump(&sensors) { JUMP_LEN } else { 1 };
+            if !ground(pos) {
+                return Err(pos);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Script {
+    type Err = ScriptError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut instructions = Vec::new();
+        let mut lines = s.lines().map(str::trim).enumerate();
+        while let Some((ix, line)) = lines.next() {
+            let mode = match line {
+                "" => continue,
+                "WALK" => Mode::Walk,
+                "RUN" => Mode::Run,
+                _ => {
+                    let bad = || ScriptError::BadLine(ix + 1, line.to_string());
+                    let parts: Vec<&str> = line.split_whitespace().collect();
+                    if parts.len() != 3 {
+                        return Err(bad());
+                    }
+                    let op = match parts[0] {
+                        "AND" => OpKind::And,
+                        "OR" => OpKind::Or,
+                        "NOT" => OpKind::Not,
+                        _ => return Err(bad()),
+                    };
+                    let src = parts[1].parse().map_err(|_| bad())?;
+                    let dst = parts[2].parse().map_err(|_| bad())?;
+                    instructions.push(Instruction::new(op, src, dst));
+                    continue;
+                }
+            };
+            if let Some((ix, extra)) = lines.find(|l| !l.1.is_empty()) {
+                return Err(ScriptError::AfterMode(ix + 1, extra.to_string()));
+            }
+            return Self::checked(instructions, mode);
+        }
+        Err(ScriptError::MissingMode)
+    }
+}
+
+/// The text the springdroid expects, ending in the mode line.
+impl fmt::Display for Script {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for i in &self.instructions {
+            writeln!(f, "{}", i)?;
+        }
+        writeln!(f, "{}", self.mode)
+    }
+}
+
+/// Parses a hull as drawn by the springdroid: `#` is ground, anything else is a hole.
+pub fn parse_hull(s: &str) -> Vec<bool> {
+    s.trim().chars().map(|c| c == '#').collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const WALK_SCRIPT: &str = "OR A J\nAND B J\nAND C J\nNOT J J\nAND D J\nWALK\n";
+
+    #[test]
+    pub fn round_trip() {
+        let s: Script = WALK_SCRIPT.parse().unwrap();
+        assert_eq!(s.instructions.len(), 5);
+        assert_eq!(s.mode, Mode::Walk);
+        assert_eq!(s.to_string(), WALK_SCRIPT);
+    }
+
+    #[test]
+    pub fn validation() {
+        assert_eq!(
+            "NOT E J\nWALK".parse::<Script>(),
+            Err(ScriptError::SensorUnavailable(1, Reg::Sensor(4), Mode::Walk))
+        );
+        assert!("NOT E J\nRUN".parse::<Script>().is_ok());
+        assert_eq!(
+            "NOT J A\nWALK".parse::<Script>(),
+            Err(ScriptError::NotWritable(1, Reg::Sensor(0)))
+        );
+        assert_eq!(
+            "NOT A J\nJUMP A J\nWALK".parse::<Script>(),
+            Err(ScriptError::BadLine(2, "JUMP A J".to_string()))
+        );
+        assert_eq!("NOT A J\n".parse::<Script>(), Err(ScriptError::MissingMode));
+        assert_eq!(
+            "NOT A J\nWALK\n\nNOT B J\n".parse::<Script>(),
+            Err(ScriptError::AfterMode(4, "NOT B J".to_string()))
+        );
+        assert!("NOT A J\nWALK\n\n".parse::<Script>().is_ok());
+        let long = "NOT A J\n".repeat(16) + "WALK";
+        assert_eq!(long.parse::<Script>(), Err(ScriptError::TooLong(16)));
+    }
+
+    #[test]
+    pub fn simulation() {
+        let s: Script = WALK_SCRIPT.parse().unwrap();
+        assert_eq!(s.simulate(&parse_hull("#####.###########")), Ok(()));
+        assert_eq!(s.simulate(&parse_hull("#####...#########")), Ok(()));
+        assert_eq!(s.simulate(&parse_hull("#####..#.########")), Ok(()));
+        //jumps the first hole, then can't see far enough to jump the second.
+        assert_eq!(s.simulate(&parse_hull("#####.#.#...#####")), Err(7));
+    }
+}
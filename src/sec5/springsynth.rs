@@ -0,0 +1,360 @@
+use super::springscript::{Instruction, Mode, OpKind, Reg, Script, MAX_INSTRUCTIONS};
+use crate::comp::Computer;
+use std::collections::HashMap;
+
+const JUMP_LEN: usize = 4;
+const MAX_ASSIGNMENTS: usize = 10_000;
+const MAX_COVER_NODES: usize = 200_000;
+
+/// Sensor readings as bits: bit `n` is set when sensor `n` (`A` is 0) sees ground.
+type Pattern = u16;
+
+/// A product term or sum clause: each literal is a sensor and whether it's un-negated.
+type Literals = Vec<(u8, bool)>;
+
+/// Finds the hull the droid fell into from the springdroid's report. The hull row is the
+/// first line of the first frame which has ground in it.
+pub fn failing_hull(output: &str) -> Option<Vec<bool>> {
+    output
+        .lines()
+        .skip_while(|l| !l.starts_with("Didn't make it across"))
+        .find(|l| l.contains('#') && l.chars().all(|c| c == '#' || c == '.'))
+        .map(|l| l.chars().map(|c| c == '#').collect())
+}
+
+/// Runs `script` on the springdroid, returning the hull damage or the hull that it fell into.
+pub fn try_script(c: &Computer, script: &Script) -> Result<i32, Vec<bool>> {
+    let mut c = c.clone();
+    c.with_string_input(&script.to_string()).run();
+    match c.get_output().iter().find(|&&x| x > 255) {
+        Some(&d) => Ok(d),
+        None => Err(failing_hull(&c.output_as_string()).expect("Unexpected springdroid output")),
+    }
+}
+
+/// Repeatedly synthesizes scripts which survive every hull seen so far, adding each new
+/// failing hull as a constraint, until one makes it across.
+pub fn solve(c: &Computer, mode: Mode) -> (Script, i32) {
+    let mut hulls: Vec<Vec<bool>> = Vec::new();
+    loop {
+        let script = synthesize(&hulls, mode).expect("No springscript fits these hulls");
+        match try_script(c, &script) {
+            Ok(damage) => break (script, damage),
+            Err(hull) => {
+                assert!(!hulls.contains(&hull), "Simulator disagrees with the springdroid");
+                hulls.push(hull);
+            }
+        }
+    }
+}
+
+/// Finds a script of at most 15 instructions which gets across every hull in `hulls`.
+pub fn synthesize(hulls: &[Vec<bool>], mode: Mode) -> Option<Script> {
+    let range = mode.sensor_range();
+    let mut budget = MAX_ASSIGNMENTS;
+    let mut assignment = HashMap::new();
+    let mut found = |a: &HashMap<Pattern, bool>| {
+        let script = Script::new(minimal_program(a, range), mode);
+        if script.instructions.len() <= MAX_INSTRUCTIONS
+            && hulls.iter().all(|h| script.simulate(h).is_ok())
+        {
+            Some(script)
+        } else {
+            None
+        }
+    };
+    assign_decisions(hulls, range, (0, 0), &mut assignment, &mut found, &mut budget)
+}
+
+fn pattern(hull: &[bool], pos: usize, range: usize) -> Pattern {
+    (0..range)
+        .filter(|&d| hull.get(pos + 1 + d).cloned().unwrap_or(true))
+        .fold(0, |p, d| p | 1 << d)
+}
+
+/// Depth-first search for jump decisions (per sensor pattern) that get the droid across
+/// every hull. Decisions are only made for patterns the droid actually meets, preferring to
+/// walk, and each complete set (up to `budget` of them) is offered to `found`.
+fn assign_decisions<F>(
+    hulls: &[Vec<bool>],
+    range: usize,
+    (hull_ix, pos): (usize, usize),
+    assignment: &mut HashMap<Pattern, bool>,
+    found: &mut F,
+    budget: &mut usize,
+) -> Option<Script>
+where
+    F: FnMut(&HashMap<Pattern, bool>) -> Option<Script>,
+{
+    let hull = match hulls.get(hull_ix) {
+        None => {
+            *budget -= 1;
+            return found(assignment);
+        }
+        Some(h) => h,
+    };
+    if pos >= hull.len() {
+        return assign_decisions(hulls, range, (hull_ix + 1, 0), assignment, found, budget);
+    }
+    let p = pattern(hull, pos, range);
+    let ground = |ix: usize| hull.get(ix).cloned().unwrap_or(true);
+    let step = |jump: bool| pos + if jump { JUMP_LEN } else { 1 };
+    if let Some(&jump) = assignment.get(&p) {
+        if !ground(step(jump)) {
+            return None;
+        }
+        return assign_decisions(hulls, range, (hull_ix, step(jump)), assignment, found, budget);
+    }
+    for &jump in &[false, true] {
+        if !ground(step(jump)) || *budget == 0 {
+            continue;
+        }
+        assignment.insert(p, jump);
+        let ans = assign_decisions(hulls, range, (hull_ix, step(jump)), assignment, found, budget);
+        assignment.remove(&p);
+        if ans.is_some() {
+            return ans;
+        }
+    }
+    None
+}
+
+/// The shortest springscript found for a function that agrees with `assignment`, trying
+/// both sum-of-products and product-of-sums forms.
+fn minimal_program(assignment: &HashMap<Pattern, bool>, range: usize) -> Vec<Instruction> {
+    let on: Vec<Pattern> = assignment.iter().filter(|x| *x.1).map(|x| *x.0).collect();
+    let off: Vec<Pattern> = assignment.iter().filter(|x| !*x.1).map(|x| *x.0).collect();
+    let sop = min_cover(&on, &off, range, compile_sop);
+    let pos = min_cover(&off, &on, range, |terms: &[Literals]| {
+        let clauses: Vec<Literals> = terms
+            .iter()
+            .map(|t| t.iter().map(|&(s, v)| (s, !v)).collect())
+            .collect();
+        compile_pos(&clauses)
+    });
+    if sop.len() <= pos.len() {
+        sop
+    } else {
+        pos
+    }
+}
+
+/// Prime implicants of the function which is true on `on` and false on `off`.
+fn prime_implicants(on: &[Pattern], off: &[Pattern], range: usize) -> Vec<(Pattern, Pattern)> {
+    let all: Pattern = (1 << range) - 1;
+    let is_implicant = |m: Pattern, v: Pattern| off.iter().all(|&p| p & m != v);
+    let mut primes = Vec::new();
+    for m in 0..=all {
+        //enumerate the subsets v of m
+        let mut v = m;
+        loop {
+            let covers_on = on.iter().any(|&p| p & m == v);
+            if covers_on
+                && is_implicant(m, v)
+                && (0..range).all(|b| m & 1 << b == 0 || !is_implicant(m & !(1 << b), v & !(1 << b)))
+            {
+                primes.push((m, v));
+            }
+            if v == 0 {
+                break;
+            }
+            v = (v - 1) & m;
+        }
+    }
+    primes.sort_by_key(|(m, _)| m.count_ones());
+    primes
+}
+
+fn as_literals((m, v): (Pattern, Pattern), range: usize) -> Literals {
+    (0..range)
+        .filter(|&b| m & 1 << b != 0)
+        .map(|b| (b as u8, v & 1 << b != 0))
+        .collect()
+}
+
+/// Branch and bound search for the set of prime implicants covering `on` which compiles to
+/// the fewest instructions.
+fn min_cover<F>(on: &[Pattern], off: &[Pattern], range: usize, compile: F) -> Vec<Instruction>
+where
+    F: Fn(&[Literals]) -> Vec<Instruction>,
+{
+    let primes = prime_implicants(on, off, range);
+    let mut best: Option<Vec<Instruction>> = None;
+    let mut nodes = 0;
+    let mut chosen: Vec<(Pattern, Pattern)> = Vec::new();
+    cover_from(on, &primes, range, &compile, &mut chosen, &mut best, &mut nodes);
+    best.unwrap_or_else(|| compile(&[]))
+}
+
+fn cover_from<F>(
+    on: &[Pattern],
+    primes: &[(Pattern, Pattern)],
+    range: usize,
+    compile: &F,
+    chosen: &mut Vec<(Pattern, Pattern)>,
+    best: &mut Option<Vec<Instruction>>,
+    nodes: &mut usize,
+) where
+    F: Fn(&[Literals]) -> Vec<Instruction>,
+{
+    *nodes += 1;
+    let terms: Vec<Literals> = chosen.iter().map(|&c| as_literals(c, range)).collect();
+    let prog = compile(&terms);
+    if best.as_ref().is_some_and(|b| prog.len() >= b.len()) || *nodes > MAX_COVER_NODES {
+        return;
+    }
+    let uncovered = on
+        .iter()
+        .find(|&&p| !chosen.iter().any(|&(m, v)| p & m == v));
+    match uncovered {
+        None => *best = Some(prog),
+        Some(&p) => {
+            for &(m, v) in primes.iter().filter(|&&(m, v)| p & m == v) {
+                chosen.push((m, v));
+                cover_from(on, primes, range, compile, chosen, best, nodes);
+                chosen.pop();
+            }
+        }
+    }
+}
+
+fn ins(op: OpKind, s: u8, dst: Reg) -> Instruction {
+    Instruction::new(op, Reg::Sensor(s), dst)
+}
+
+/// Loads sensor `s` into `reg`. Registers start each tick false, so a fresh one just needs
+/// an `OR`.
+fn load(s: u8, reg: Reg, fresh: bool, out: &mut Vec<Instruction>) {
+    if fresh {
+        out.push(ins(OpKind::Or, s, reg));
+    } else {
+        out.push(ins(OpKind::Not, s, reg));
+        out.push(Instruction::new(OpKind::Not, reg, reg));
+    }
+}
+
+/// Computes the AND (`conj`) or OR of `lits` into `reg`. Only the first literal of a chain
+/// can be negated, so the negated literals are gathered together using De Morgan's laws.
+fn chain(lits: &[(u8, bool)], conj: bool, reg: Reg, fresh: bool, out: &mut Vec<Instruction>) {
+    let (op, other) = if conj {
+        (OpKind::And, OpKind::Or)
+    } else {
+        (OpKind::Or, OpKind::And)
+    };
+    let negs: Vec<u8> = lits.iter().filter(|l| !l.1).map(|l| l.0).collect();
+    let poss: Vec<u8> = lits.iter().filter(|l| l.1).map(|l| l.0).collect();
+    let rest = match (negs.len(), poss.split_first()) {
+        (0, Some((&first, rest))) => {
+            load(first, reg, fresh, out);
+            rest
+        }
+        (0, None) => unreachable!("Empty literal chain"),
+        (1, _) => {
+            out.push(ins(OpKind::Not, negs[0], reg));
+            &poss[..]
+        }
+        _ => {
+            load(negs[0], reg, fresh, out);
+            negs[1..].iter().for_each(|&n| out.push(ins(other, n, reg)));
+            out.push(Instruction::new(OpKind::Not, reg, reg));
+            &poss[..]
+        }
+    };
+    rest.iter().for_each(|&s| out.push(ins(op, s, reg)));
+}
+
+/// `J = OR of terms`, each term an AND of literals.
+fn compile_sop(terms: &[Literals]) -> Vec<Instruction> {
+    compile_two_level(terms, true)
+}
+
+/// `J = AND of clauses`, each clause an OR of literals.
+fn compile_pos(clauses: &[Literals]) -> Vec<Instruction> {
+    compile_two_level(clauses, false)
+}
+
+fn compile_two_level(groups: &[Literals], sop: bool) -> Vec<Instruction> {
+    let (outer, inner_conj) = if sop {
+        (OpKind::Or, true)
+    } else {
+        (OpKind::And, false)
+    };
+    let mut out = Vec::new();
+    //constant functions: an empty term is always true, an empty clause always false.
+    if groups.iter().any(Vec::is_empty) {
+        if sop {
+            out.push(Instruction::new(OpKind::Not, Reg::T, Reg::J));
+        }
+        return out;
+    }
+    if !sop && groups.is_empty() {
+        out.push(Instruction::new(OpKind::Not, Reg::T, Reg::J));
+        return out;
+    }
+    let mut t_fresh = true;
+    for (ix, g) in groups.iter().enumerate() {
+        if ix == 0 {
+            chain(g, inner_conj, Reg::J, true, &mut out);
+        } else if let [(s, true)] = g[..] {
+            out.push(ins(outer, s, Reg::J));
+        } else {
+            chain(g, inner_conj, Reg::T, t_fresh, &mut out);
+            t_fresh = false;
+            out.push(Instruction::new(outer, Reg::T, Reg::J));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::springscript::parse_hull;
+    use super::*;
+
+    #[test]
+    pub fn parse_failure() {
+        let out = "Input instructions:\n\nWalking...\n\nDidn't make it across:\n\n\
+                   .................\n.................\n@................\n#####.###########\n\n\
+                   .................\n.................\n.................\n#####@###########\n";
+        assert_eq!(failing_hull(out), Some(parse_hull("#####.###########")));
+        assert_eq!(failing_hull("Walking...\n"), None);
+    }
+
+    #[test]
+    pub fn synthesizes_for_hulls() {
+        let hulls: Vec<Vec<bool>> = ["#####.###########", "#####...#########", "#####..#.########"]
+            .iter()
+            .map(|h| parse_hull(h))
+            .collect();
+        let s = synthesize(&hulls, Mode::Walk).unwrap();
+        assert!(s.validate().is_ok());
+        assert!(hulls.iter().all(|h| s.simulate(h).is_ok()));
+
+        let run_hulls: Vec<Vec<bool>> = ["#####.#.##..#.###", "#####..####.#..##"]
+            .iter()
+            .map(|h| parse_hull(h))
+            .collect();
+        let s = synthesize(&run_hulls, Mode::Run).unwrap();
+        assert!(s.validate().is_ok());
+        assert!(run_hulls.iter().all(|h| s.simulate(h).is_ok()));
+    }
+
+    #[test]
+    pub fn compiles_two_level_forms() {
+        //(!A + !B + !C) & D & (E + H)
+        let prog = compile_pos(&[
+            vec![(0, false), (1, false), (2, false)],
+            vec![(3, true)],
+            vec![(4, true), (7, true)],
+        ]);
+        let s = Script::new(prog, Mode::Run);
+        assert_eq!(s.instructions.len(), 8);
+        for p in 0..512_u16 {
+            let sensors: Vec<bool> = (0..9).map(|b| p & 1 << b != 0).collect();
+            let expected = !(sensors[0] && sensors[1] && sensors[2])
+                && sensors[3]
+                && (sensors[4] || sensors[7]);
+            assert_eq!(s.should_jump(&sensors), expected);
+        }
+    }
+}
@@ -0,0 +1,199 @@
+use crate::utils::points::{point_map_bounding_box, Aabb, Point};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::hash::BuildHasher;
+use std::ops::{Index, IndexMut};
+
+/// A dense rectangle of cells. Rows run down the screen, so like `as_point_map`, y increases
+/// going down; `origin` is the top left cell.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Grid<T> {
+    origin: Point,
+    width: usize,
+    height: usize,
+    cells: Vec<T>,
+}
+
+impl<T> Grid<T> {
+    /// A `width` by `height` grid with its top left cell at the origin.
+    pub fn from_fn(width: usize, height: usize, mut f: impl FnMut(Point) -> T) -> Self {
+        let cells = (0..height)
+            .flat_map(|y| (0..width).map(move |x| Point(x as isize, y as isize)))
+            .map(&mut f)
+            .collect();
+        Self {
+            origin: Point(0, 0),
+            width,
+            height,
+            cells,
+        }
+    }
+    pub fn new(width: usize, height: usize, fill: T) -> Self
+    where
+        T: Clone,
+    {
+        Self::from_fn(width, height, |_| fill.clone())
+    }
+    /// The same cells, with the top left one at `origin`.
+    pub fn with_origin(mut self, origin: Point) -> Self {
+        self.origin = origin;
+        self
+    }
+    pub fn origin(&self) -> Point {
+        self.origin
+    }
+    pub fn width(&self) -> usize {
+        self.width
+    }
+    pub fn height(&self) -> usize {
+        self.height
+    }
+    pub fn bounds(&self) -> Aabb {
+        let far = Point(self.width as isize - 1, self.height as isize - 1);
+        Aabb::new(self.origin).extend(self.origin + far)
+    }
+    fn offset(&self, p: Point) -> Option<usize> {
+        let rel = p - self.origin;
+        let x = usize::try_from(rel.0).ok().filter(|&x| x < self.width)?;
+        let y = usize::try_from(rel.1).ok().filter(|&y| y < self.height)?;
+        Some(y * self.width + x)
+    }
+    fn point(&self, offset: usize) -> Point {
+        let x = (offset % self.width) as isize;
+        let y = (offset / self.width) as isize;
+        self.origin + Point(x, y)
+    }
+    pub fn contains(&self, p: Point) -> bool {
+        self.offset(p).is_some()
+    }
+    pub fn get(&self, p: Point) -> Option<&T> {
+        self.offset(p).map(|o| &self.cells[o])
+    }
+    pub fn get_mut(&mut self, p: Point) -> Option<&mut T> {
+        self.offset(p).map(move |o| &mut self.cells[o])
+    }
+    /// The rows, top first.
+    pub fn rows(&self) -> impl DoubleEndedIterator<Item = &[T]> {
+        self.cells.chunks(self.width.max(1))
+    }
+    /// Each cell and where it is, a row at a time.
+    pub fn cells(&self) -> impl Iterator<Item = (Point, &T)> {
+        self.cells.iter().enumerate().map(move |(o, t)| (self.point(o), t))
+    }
+    pub fn points(&self) -> impl Iterator<Item = Point> + '_ {
+        (0..self.cells.len()).map(move |o| self.point(o))
+    }
+    /// Where the first cell (in reading order) matching `f` is.
+    pub fn position(&self, f: impl FnMut(&T) -> bool) -> Option<Point> {
+        self.cells.iter().position(f).map(|o| self.point(o))
+    }
+    /// The up to 4 neighbours of `p` which are in the grid.
+    pub fn neighbours(&self, p: Point) -> impl Iterator<Item = Point> + '_ {
+        p.neighbours().to_vec().into_iter().filter(move |&n| self.contains(n))
+    }
+    pub fn map<U>(&self, f: impl FnMut(&T) -> U) -> Grid<U> {
+        Grid {
+            origin: self.origin,
+            width: self.width,
+            height: self.height,
+            cells: self.cells.iter().map(f).collect(),
+        }
+    }
+    /// A row of text for each row of cells, top (lowest y) first. That's the opposite way up
+    /// to `render_char_map`, which puts the highest y first; for maps like that, render
+    /// `rows().rev()` instead.
+    pub fn render_with(&self, mut f: impl FnMut(&T) -> char) -> String {
+        self.rows()
+            .map(|r| r.iter().map(&mut f).chain(Some('\n')).collect::<String>())
+            .collect()
+    }
+    pub fn to_map(&self) -> HashMap<Point, T>
+    where
+        T: Clone,
+    {
+        self.cells().map(|(p, t)| (p, t.clone())).collect()
+    }
+    /// A grid just covering the points of `m`, with `default` where it has nothing. Points
+    /// keep their coordinates, so a map built with y going up comes out upside down from
+    /// `render`.
+    pub fn from_map<S: BuildHasher>(m: &HashMap<Point, T, S>, default: T) -> Self
+    where
+        T: Clone,
+    {
+        if m.is_empty() {
+            return Self::new(0, 0, default);
+        }
+        let bb = point_map_bounding_box(m);
+        Self::from_fn(bb.width(), bb.height(), |p| {
+            m.get(&(p + bb.bottom_left)).unwrap_or(&default).clone()
+        })
+        .with_origin(bb.bottom_left)
+    }
+}
+
+impl Grid<char> {
+    /// Reads text a line at a time. Short lines are padded out with spaces.
+    pub fn parse(s: &str) -> Self {
+        Self::parse_padded(s, ' ')
+    }
+    /// As `parse`, padding short lines out with `pad`, for maps where a space means something.
+    pub fn parse_padded(s: &str, pad: char) -> Self {
+        let lines: Vec<Vec<char>> = s.lines().map(|l| l.chars().collect()).collect();
+        let width = lines.iter().map(Vec::len).max().unwrap_or(0);
+        Self::from_fn(width, lines.len(), |p| {
+            lines[p.1 as usize].get(p.0 as usize).cloned().unwrap_or(pad)
+        })
+    }
+    pub fn render(&self) -> String {
+        self.render_with(|&c| c)
+    }
+}
+
+impl<T> Index<Point> for Grid<T> {
+    type Output = T;
+    fn index(&self, p: Point) -> &T {
+        self.get(p)
+            .unwrap_or_else(|| panic!("{:?} is outside the grid", p))
+    }
+}
+
+impl<T> IndexMut<Point> for Grid<T> {
+    fn index_mut(&mut self, p: Point) -> &mut T {
+        self.get_mut(p)
+            .unwrap_or_else(|| panic!("{:?} is outside the grid", p))
+    }
+}
+
+#[test]
+pub fn grid_tests() {
+    use crate::utils::points::as_point_map;
+    let text = "#.#\n..\n#..\n";
+    let mut g = Grid::parse(text);
+    assert_eq!((g.width(), g.height()), (3, 3));
+    assert_eq!(g[Point(1, 0)], '.');
+    assert_eq!(g[Point(2, 1)], ' ');
+    assert_eq!(g.get(Point(3, 0)), None);
+    assert!(!g.contains(Point(0, -1)));
+    assert_eq!(g.position(|&c| c == '#'), Some(Point(0, 0)));
+    assert_eq!(g.rows().nth(2), Some(&['#', '.', '.'][..]));
+    assert_eq!(g.neighbours(Point(0, 0)).count(), 2);
+    assert_eq!(g.neighbours(Point(1, 1)).count(), 4);
+    g[Point(2, 1)] = '.';
+    assert_eq!(g.render(), "#.#\n...\n#..\n");
+    let walls = g.map(|&c| c == '#');
+    assert_eq!(walls.cells().filter(|c| *c.1).count(), 3);
+    assert_eq!(g.to_map(), as_point_map(&g.render()));
+    assert_eq!(Grid::parse_padded(text, '?').render(), "#.#\n..?\n#..\n");
+
+    let m: HashMap<Point, char> = [(Point(-2, 5), 'a'), (Point(0, 4), 'b')].iter().cloned().collect();
+    let g = Grid::from_map(&m, '.');
+    assert_eq!(g.origin(), Point(-2, 4));
+    assert_eq!(g.bounds().top_right, Point(0, 5));
+    assert_eq!(g.render(), "..b\na..\n");
+    //render_char_map has y going up.
+    let up: String = g.rows().rev().map(|r| format!("\n{}", r.iter().collect::<String>())).collect();
+    assert_eq!(up, crate::utils::points::render_char_map_w(&m, 1, '.'));
+    assert_eq!(g[Point(-2, 5)], 'a');
+    let back: HashMap<Point, char> = g.cells().filter(|c| *c.1 != '.').map(|(p, &c)| (p, c)).collect();
+    assert_eq!(back, m);
+}
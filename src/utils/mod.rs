@@ -1,6 +1,8 @@
 pub mod algorithms;
+pub mod grid;
 pub mod iter;
 pub mod log;
 pub mod nums;
+pub mod ocr;
 pub mod points;
 pub mod prelude;
@@ -0,0 +1,187 @@
+use crate::utils::points::{render_char_map, Point};
+use std::collections::HashMap;
+use std::hash::BuildHasher;
+
+/// The 4x6 letters most puzzles draw, with blank columns at the sides trimmed off.
+const FONT_6: &[(char, &str)] = &[
+    ('A', ".##.\n#..#\n#..#\n####\n#..#\n#..#"),
+    ('B', "###.\n#..#\n###.\n#..#\n#..#\n###."),
+    ('C', ".##.\n#..#\n#...\n#...\n#..#\n.##."),
+    ('E', "####\n#...\n###.\n#...\n#...\n####"),
+    ('F', "####\n#...\n###.\n#...\n#...\n#..."),
+    ('G', ".##.\n#..#\n#...\n#.##\n#..#\n.###"),
+    ('H', "#..#\n#..#\n####\n#..#\n#..#\n#..#"),
+    ('I', "###\n.#.\n.#.\n.#.\n.#.\n###"),
+    ('J', "..##\n...#\n...#\n...#\n#..#\n.##."),
+    ('K', "#..#\n#.#.\n##..\n#.#.\n#.#.\n#..#"),
+    ('L', "#...\n#...\n#...\n#...\n#...\n####"),
+    ('O', ".##.\n#..#\n#..#\n#..#\n#..#\n.##."),
+    ('P', "###.\n#..#\n#..#\n###.\n#...\n#..."),
+    ('R', "###.\n#..#\n#..#\n###.\n#.#.\n#..#"),
+    ('S', ".###\n#...\n#...\n.##.\n...#\n###."),
+    ('U', "#..#\n#..#\n#..#\n#..#\n#..#\n.##."),
+    ('Y', "#...#\n#...#\n.#.#.\n..#..\n..#..\n..#.."),
+    ('Z', "####\n...#\n..#.\n.#..\n#...\n####"),
+];
+
+/// The larger 6x10 letters.
+const FONT_10: &[(char, &str)] = &[
+    ('A', "..##..\n.#..#.\n#....#\n#....#\n#....#\n######\n#....#\n#....#\n#....#\n#....#"),
+    ('B', "#####.\n#....#\n#....#\n#....#\n#####.\n#....#\n#....#\n#....#\n#....#\n#####."),
+    ('C', ".####.\n#....#\n#.....\n#.....\n#.....\n#.....\n#.....\n#.....\n#....#\n.####."),
+    ('E', "######\n#.....\n#.....\n#.....\n#####.\n#.....\n#.....\n#.....\n#.....\n######"),
+    ('F', "######\n#.....\n#.....\n#.....\n#####.\n#.....\n#.....\n#.....\n#.....\n#....."),
+    ('G', ".####.\n#....#\n#.....\n#.....\n#.....\n#..###\n#....#\n#....#\n#...##\n.###.#"),
+    ('H', "#....#\n#....#\n#....#\n#....#\n######\n#....#\n#....#\n#....#\n#....#\n#....#"),
+    ('J', "...###\n....#.\n....#.\n....#.\n....#.\n....#.\n....#.\n#...#.\n#...#.\n.###.."),
+    ('K', "#....#\n#...#.\n#..#..\n#.#...\n##....\n##....\n#.#...\n#..#..\n#...#.\n#....#"),
+    ('L', "#.....\n#.....\n#.....\n#.....\n#.....\n#.....\n#.....\n#.....\n#.....\n######"),
+    ('N', "#....#\n##...#\n##...#\n#.#..#\n#.#..#\n#..#.#\n#..#.#\n#...##\n#...##\n#....#"),
+    ('P', "#####.\n#....#\n#....#\n#....#\n#####.\n#.....\n#.....\n#.....\n#.....\n#....."),
+    ('R', "#####.\n#....#\n#....#\n#....#\n#####.\n#..#..\n#...#.\n#...#.\n#....#\n#....#"),
+    ('X', "#....#\n#....#\n.#..#.\n.#..#.\n..##..\n..##..\n.#..#.\n.#..#.\n#....#\n#....#"),
+    ('Z', "######\n.....#\n.....#\n....#.\n...#..\n..#...\n.#....\n#.....\n#.....\n######"),
+];
+
+/// A letter which couldn't be read.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Glyph {
+    /// Which letter of the text it is.
+    pub index: usize,
+    /// The column of the image its leftmost lit pixel is in.
+    pub column: usize,
+    /// Its pixels, as `#` and `.`.
+    pub rows: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Reading {
+    /// The letters, with `?` for any that weren't recognised.
+    pub text: String,
+    pub unrecognised: Vec<Glyph>,
+}
+
+impl Reading {
+    /// The text, if every letter was recognised.
+    pub fn into_result(self) -> Result<String, Self> {
+        if self.unrecognised.is_empty() {
+            Ok(self.text)
+        } else {
+            Err(self)
+        }
+    }
+}
+
+fn is_lit(c: char) -> bool {
+    c == '█' || c == '#'
+}
+
+/// Reads letters from rows of pixels, top row first. Pixels are lit if they're `█` or `#`.
+/// Letters are split up by columns with nothing lit.
+pub fn read_grid(grid: &[Vec<char>]) -> Reading {
+    let font: HashMap<&str, char> = FONT_6
+        .iter()
+        .chain(FONT_10.iter())
+        .map(|&(c, g)| (g, c))
+        .collect();
+    let lit_rows: Vec<usize> = (0..grid.len())
+        .filter(|&y| grid[y].iter().any(|&c| is_lit(c)))
+        .collect();
+    let mut ans = Reading {
+        text: String::new(),
+        unrecognised: Vec::new(),
+    };
+    let (top, bottom) = match (lit_rows.first(), lit_rows.last()) {
+        (Some(&t), Some(&b)) => (t, b),
+        _ => return ans,
+    };
+    let width = grid.iter().map(Vec::len).max().unwrap_or(0);
+    let lit = |x: usize, y: usize| grid[y].get(x).cloned().is_some_and(is_lit);
+    let column_lit = |x: usize| (top..=bottom).any(|y| lit(x, y));
+    let mut x = 0;
+    while x < width {
+        if !column_lit(x) {
+            x += 1;
+            continue;
+        }
+        let start = x;
+        while x < width && column_lit(x) {
+            x += 1;
+        }
+        let rows: Vec<String> = (top..=bottom)
+            .map(|y| (start..x).map(|x| if lit(x, y) { '#' } else { '.' }).collect())
+            .collect();
+        match font.get(rows.join("\n").as_str()) {
+            Some(&c) => ans.text.push(c),
+            None => {
+                ans.unrecognised.push(Glyph {
+                    index: ans.text.chars().count(),
+                    column: start,
+                    rows,
+                });
+                ans.text.push('?');
+            }
+        }
+    }
+    ans
+}
+
+/// Reads letters from lines of text.
+pub fn read_str(s: &str) -> Reading {
+    let grid: Vec<Vec<char>> = s.lines().map(|l| l.chars().collect()).collect();
+    read_grid(&grid)
+}
+
+/// Reads letters from a map the right way up as `render_char_map` draws it.
+pub fn read_point_map<S: BuildHasher>(m: &HashMap<Point, char, S>) -> Reading {
+    read_str(&render_char_map(m))
+}
+
+#[test]
+pub fn ocr_tests() {
+    use crate::utils::points::as_point_map;
+    //day 11's output, with the unpainted panels around it.
+    let hull = "
+░█░░░░███░░████░████░░██░░░██░░████░████░░
+ █░░░░█░░█░░░░█░█░░░░█░░█░█░░█░█░░░░█░░░░░░
+ █░░░░█░░█░░░█░░███░░█░░░░█░░░░███░░███░░░░
+░█░░░░███░░░█░░░█░░░░█░░░░█░██░█░░░░█░░░░░
+░█░░░░█░█░░█░░░░█░░░░█░░█░█░░█░█░░░░█░░░░
+ ████░█░░█░████░████░░██░░░███░█░░░░████░  ";
+    assert_eq!(read_str(hull).into_result(), Ok("LRZECGFE".to_string()));
+    let grid: Vec<Vec<char>> = FONT_6
+        .iter()
+        .map(|f| f.1.lines().map(|l| l.chars().collect::<Vec<_>>()))
+        .fold(vec![Vec::new(); 6], |mut g, rows| {
+            for (y, r) in rows.enumerate() {
+                g[y].extend(r);
+                g[y].push('.');
+            }
+            g
+        });
+    assert_eq!(read_grid(&grid).text, "ABCEFGHIJKLOPRSUYZ");
+    let big: String = FONT_10
+        .iter()
+        .filter(|f| "HELLO".contains(f.0) || f.0 == 'X')
+        .map(|f| f.1)
+        .fold(vec![String::new(); 10], |mut g, f| {
+            for (y, r) in f.lines().enumerate() {
+                g[y] += r;
+                g[y] += "..";
+            }
+            g
+        })
+        .join("\n");
+    assert_eq!(read_str(&big).text, "EHLX");
+    //as_point_map has y going down, so flip it to come out the right way up.
+    let odd = "#.#\n.#.\n#.#\n#.#\n.#.\n#.#";
+    let m: HashMap<Point, char> = as_point_map(odd)
+        .into_iter()
+        .map(|(p, c)| (Point(p.0 + 7, 5 - p.1), c))
+        .collect();
+    let r = read_point_map(&m);
+    assert_eq!(r.text, "?");
+    let rows = odd.lines().map(String::from).collect();
+    assert_eq!(r.unrecognised, vec![Glyph { index: 0, column: 0, rows }]);
+    assert_eq!(read_str("  \n").text, "");
+}